@@ -0,0 +1,141 @@
+use std::f64::consts::PI;
+
+use volv::render::{Renderer, ViewportCoord};
+use volv::tree::{BodyId, BodyRef};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+const BACKGROUND_COLOR: &str = "black";
+const LARGE_BODY_COLOR: &str = "#d0c8b0";
+const SMALL_BODY_COLOR: &str = "#80c0ff";
+const SOI_COLOR: &str = "rgba(255, 255, 255, 0.15)";
+const ORBIT_COLOR: &str = "rgba(255, 255, 255, 0.4)";
+const SELECTION_COLOR: &str = "#ffd040";
+/// The gap in pixels between a selected body and its selection ring
+const SELECTION_GAP: f64 = 3.0;
+/// The minimum radius of a body in pixels, so that tiny bodies remain visible
+const MIN_BODY_RADIUS: f64 = 1.5;
+
+/// Renders to a 2D canvas, mapping the viewport onto the whole canvas.
+///
+/// The y-axis of the viewport points up on the canvas.
+#[derive(Debug)]
+pub struct CanvasRenderer {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    width: f64,
+    height: f64,
+    /// The body highlighted with a selection ring
+    selected: Option<BodyId>,
+}
+
+impl CanvasRenderer {
+    /// Returns `None` if the 2D context is not available.
+    pub fn new(canvas: HtmlCanvasElement) -> Option<Self> {
+        let context = canvas
+            .get_context("2d")
+            .ok()
+            .flatten()?
+            .dyn_into::<CanvasRenderingContext2d>()
+            .ok()?;
+        let (width, height) = (f64::from(canvas.width()), f64::from(canvas.height()));
+        Some(Self {
+            canvas,
+            context,
+            width,
+            height,
+            selected: None,
+        })
+    }
+
+    /// Fills the canvas with the background color.
+    ///
+    /// The size of the canvas is updated in case it has been resized.
+    pub fn clear(&mut self) {
+        self.width = f64::from(self.canvas.width());
+        self.height = f64::from(self.canvas.height());
+        self.context
+            .set_fill_style(&JsValue::from_str(BACKGROUND_COLOR));
+        self.context.fill_rect(0.0, 0.0, self.width, self.height);
+    }
+
+    pub fn set_selected(&mut self, selected: Option<BodyId>) {
+        self.selected = selected;
+    }
+
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+
+    /// Converts a position in pixels from the top-left corner of the canvas to the viewport.
+    pub fn to_viewport(&self, x: f64, y: f64) -> ViewportCoord {
+        ((x / self.width) as f32, (1.0 - y / self.height) as f32)
+    }
+
+    fn to_pixels(&self, at: ViewportCoord) -> (f64, f64) {
+        (
+            f64::from(at.0) * self.width,
+            (1.0 - f64::from(at.1)) * self.height,
+        )
+    }
+
+    fn circle(&self, center: (f64, f64), radius: f64) {
+        self.context.begin_path();
+        // the only error is a negative radius
+        let _ = self.context.arc(center.0, center.1, radius, 0.0, PI * 2.0);
+    }
+}
+
+impl Renderer for CanvasRenderer {
+    fn render_body(&mut self, body: BodyRef<'_>, at: ViewportCoord, scale: (f32, f32)) {
+        let center = self.to_pixels(at);
+        let pixels_per_length = f64::from(scale.0) * self.width;
+
+        if let BodyRef::Large(body) = body {
+            let soi = f64::from(body.grav_radius().0) * pixels_per_length;
+            if soi.is_finite() {
+                self.circle(center, soi);
+                self.context.set_stroke_style(&JsValue::from_str(SOI_COLOR));
+                self.context.stroke();
+            }
+        }
+
+        let color = match body {
+            BodyRef::Large(_) => LARGE_BODY_COLOR,
+            BodyRef::Small(_) => SMALL_BODY_COLOR,
+        };
+        let radius = (f64::from(body.radius().0) * pixels_per_length).max(MIN_BODY_RADIUS);
+        self.circle(center, radius);
+        self.context.set_fill_style(&JsValue::from_str(color));
+        self.context.fill();
+
+        if self.selected == Some(body.id()) {
+            self.circle(center, radius + SELECTION_GAP);
+            self.context
+                .set_stroke_style(&JsValue::from_str(SELECTION_COLOR));
+            self.context.stroke();
+        }
+    }
+
+    fn render_orbit(&mut self, id: BodyId, path: &[ViewportCoord]) {
+        let mut points = path.iter().map(|&point| self.to_pixels(point));
+        let first = match points.next() {
+            Some(first) => first,
+            None => return,
+        };
+
+        self.context.begin_path();
+        self.context.move_to(first.0, first.1);
+        for (x, y) in points {
+            self.context.line_to(x, y);
+        }
+        self.context
+            .set_stroke_style(&JsValue::from_str(ORBIT_COLOR));
+        self.context.set_line_width(1.0);
+        self.context.stroke();
+    }
+
+    fn pixel_size(&self) -> (f32, f32) {
+        ((1.0 / self.width) as f32, (1.0 / self.height) as f32)
+    }
+}
@@ -0,0 +1,312 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use volv::math::Time;
+use volv::render::{self, Renderer, Viewport, ViewportCoord};
+use volv::tree::{BodyId, System};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::canvas::CanvasRenderer;
+use crate::handler::Handler;
+
+/// The number of game time units that pass in one second at a warp factor of 1
+const TIME_UNITS_PER_SECOND: f64 = 1.0;
+/// The largest accepted warp factor
+const MAX_WARP: f64 = 1e4;
+/// The factor by which the viewport is scaled for each step of the mouse wheel
+const ZOOM_STEP: f32 = 1.2;
+/// The distance in pixels around a body within which a click selects it
+const PICK_TOLERANCE: f32 = 4.0;
+
+type FrameCallback = Closure<dyn FnMut(f64)>;
+
+/// A running game, which advances and renders the system on every animation frame
+#[wasm_bindgen]
+pub struct Game {
+    state: Rc<RefCell<State>>,
+    callback: Rc<RefCell<Option<FrameCallback>>>,
+    listeners: Vec<Listener>,
+}
+
+struct State {
+    system: System<Handler>,
+    renderer: CanvasRenderer,
+    viewport: Viewport,
+    /// The game time, including the fraction not yet passed to the system
+    time: f64,
+    warp: f64,
+    /// The timestamp of the last frame in milliseconds, which is `None` after stopping
+    last_frame: Option<f64>,
+    /// The ID of the requested animation frame
+    frame: Option<i32>,
+    /// The last position of the cursor while dragging
+    drag: Option<ViewportCoord>,
+    /// Whether the cursor has moved since the mouse button was pressed
+    dragged: bool,
+    selected: Option<BodyId>,
+}
+
+impl Game {
+    pub fn new(system: System<Handler>, renderer: CanvasRenderer, viewport: Viewport) -> Self {
+        let state = Rc::new(RefCell::new(State {
+            time: f64::from(system.now().0),
+            system,
+            renderer,
+            viewport,
+            warp: 1.0,
+            last_frame: None,
+            frame: None,
+            drag: None,
+            dragged: false,
+            selected: None,
+        }));
+        let callback = Rc::new(RefCell::new(None));
+
+        // the callback only holds a weak reference to itself, so dropping the game frees it
+        let weak = Rc::downgrade(&callback);
+        let frame_state = Rc::clone(&state);
+        let frame = move |timestamp: f64| {
+            let mut state = frame_state.borrow_mut();
+            state.frame = None;
+            state.tick(timestamp);
+            request_frame(&mut state, &weak);
+        };
+        *callback.borrow_mut() = Some(Closure::wrap(Box::new(frame) as Box<dyn FnMut(f64)>));
+
+        let canvas: web_sys::EventTarget = state.borrow().renderer.canvas().clone().into();
+        let window: web_sys::EventTarget = window().into();
+        let listeners = vec![
+            Listener::new(&canvas, "wheel", &state, State::on_wheel),
+            Listener::new(&canvas, "mousedown", &state, State::on_mouse_down),
+            Listener::new(&canvas, "mousemove", &state, State::on_mouse_move),
+            Listener::new(&canvas, "mouseup", &state, State::on_mouse_up),
+            Listener::new(&canvas, "mouseleave", &state, State::on_mouse_up),
+            Listener::new(&canvas, "click", &state, State::on_click),
+            Listener::new(&window, "keydown", &state, State::on_key_down),
+        ];
+
+        Self {
+            state,
+            callback,
+            listeners,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// Starts the animation loop if it is not running.
+    pub fn start(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.frame.is_none() {
+            request_frame(&mut state, &Rc::downgrade(&self.callback));
+        }
+    }
+
+    /// Stops the animation loop, pausing the game time.
+    pub fn stop(&self) {
+        let mut state = self.state.borrow_mut();
+        if let Some(frame) = state.frame.take() {
+            window()
+                .cancel_animation_frame(frame)
+                .expect("cancelAnimationFrame failed");
+        }
+        state.last_frame = None;
+    }
+
+    pub fn running(&self) -> bool {
+        self.state.borrow().frame.is_some()
+    }
+
+    /// The number of times faster than real time the game runs
+    pub fn warp(&self) -> f64 {
+        self.state.borrow().warp
+    }
+
+    /// Sets the warp factor, clamped to `0..=MAX_WARP` with NaN treated as 0.
+    ///
+    /// Returns the warp factor that was set.
+    pub fn set_warp(&self, warp: f64) -> f64 {
+        let warp = if warp.is_nan() {
+            0.0
+        } else {
+            warp.clamp(0.0, MAX_WARP)
+        };
+        self.state.borrow_mut().warp = warp;
+        warp
+    }
+
+    /// The index of the selected body, if any
+    pub fn selected(&self) -> Option<u32> {
+        self.state.borrow().selected.map(|id| id.index())
+    }
+}
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl State {
+    fn tick(&mut self, timestamp: f64) {
+        if let Some(last) = self.last_frame {
+            let elapsed = (timestamp - last) / 1000.0;
+            self.time += elapsed * self.warp * TIME_UNITS_PER_SECOND;
+        }
+        self.last_frame = Some(timestamp);
+
+        let t = Time(self.time.floor() as i32);
+        self.system.advance_detecting(t);
+        self.draw();
+    }
+
+    fn draw(&mut self) {
+        let t = Time(self.time.floor() as i32);
+        self.renderer.clear();
+        render::render(&self.system, t, &self.viewport, &mut self.renderer);
+    }
+
+    /// Redraws after the viewport changes, since the animation loop does not when stopped
+    fn redraw_if_stopped(&mut self) {
+        if self.frame.is_none() {
+            self.draw();
+        }
+    }
+
+    fn cursor(&self, event: &web_sys::MouseEvent) -> ViewportCoord {
+        self.renderer
+            .to_viewport(f64::from(event.offset_x()), f64::from(event.offset_y()))
+    }
+
+    fn on_wheel(&mut self, event: web_sys::Event) {
+        let event = match event.dyn_into::<web_sys::WheelEvent>() {
+            Ok(event) => event,
+            Err(_) => return,
+        };
+        event.prevent_default();
+
+        let factor = if event.delta_y() > 0.0 {
+            ZOOM_STEP
+        } else {
+            ZOOM_STEP.recip()
+        };
+        let cursor = self.cursor(&event);
+        self.viewport.zoom(factor, cursor);
+        self.redraw_if_stopped();
+    }
+
+    fn on_mouse_down(&mut self, event: web_sys::Event) {
+        if let Some(event) = event.dyn_ref::<web_sys::MouseEvent>() {
+            self.drag = Some(self.cursor(event));
+            self.dragged = false;
+        }
+    }
+
+    fn on_mouse_move(&mut self, event: web_sys::Event) {
+        let (event, last) = match (event.dyn_ref::<web_sys::MouseEvent>(), self.drag) {
+            (Some(event), Some(last)) => (event, last),
+            _ => return,
+        };
+        let cursor = self.cursor(event);
+        self.viewport.pan((cursor.0 - last.0, cursor.1 - last.1));
+        self.drag = Some(cursor);
+        self.dragged = true;
+        self.redraw_if_stopped();
+    }
+
+    fn on_mouse_up(&mut self, _: web_sys::Event) {
+        self.drag = None;
+    }
+
+    /// Selects the body under the cursor, unless the click ends a drag.
+    fn on_click(&mut self, event: web_sys::Event) {
+        let event = match event.dyn_ref::<web_sys::MouseEvent>() {
+            Some(event) if !self.dragged => event,
+            _ => return,
+        };
+        let t = Time(self.time.floor() as i32);
+        let cursor = self.cursor(event);
+        self.selected = render::pick(
+            &self.system,
+            t,
+            &self.viewport,
+            &self.renderer,
+            cursor,
+            PICK_TOLERANCE,
+        );
+        self.renderer.set_selected(self.selected);
+        self.redraw_if_stopped();
+    }
+
+    fn on_key_down(&mut self, event: web_sys::Event) {
+        let event = match event.dyn_ref::<web_sys::KeyboardEvent>() {
+            Some(event) => event,
+            None => return,
+        };
+        let tree = self.system.tree();
+        match event.key().as_str() {
+            "ArrowUp" => self.viewport.focus_parent(tree),
+            "ArrowDown" => self.viewport.focus_child(tree),
+            "ArrowLeft" => self.viewport.focus_sibling(tree, -1),
+            "ArrowRight" => self.viewport.focus_sibling(tree, 1),
+            _ => return,
+        }
+        event.prevent_default();
+        self.redraw_if_stopped();
+    }
+}
+
+/// An event listener that is removed when dropped
+struct Listener {
+    target: web_sys::EventTarget,
+    name: &'static str,
+    callback: Closure<dyn FnMut(web_sys::Event)>,
+}
+
+impl Listener {
+    fn new(
+        target: &web_sys::EventTarget,
+        name: &'static str,
+        state: &Rc<RefCell<State>>,
+        handler: fn(&mut State, web_sys::Event),
+    ) -> Self {
+        let state = Rc::clone(state);
+        let callback = move |event| handler(&mut state.borrow_mut(), event);
+        let callback = Closure::wrap(Box::new(callback) as Box<dyn FnMut(web_sys::Event)>);
+        target
+            .add_event_listener_with_callback(name, callback.as_ref().unchecked_ref())
+            .expect("addEventListener failed");
+        Self {
+            target: target.clone(),
+            name,
+            callback,
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = self
+            .target
+            .remove_event_listener_with_callback(self.name, self.callback.as_ref().unchecked_ref());
+    }
+}
+
+fn window() -> web_sys::Window {
+    web_sys::window().expect("No global window")
+}
+
+fn request_frame(state: &mut State, callback: &Weak<RefCell<Option<FrameCallback>>>) {
+    let callback = match callback.upgrade() {
+        Some(callback) => callback,
+        None => return,
+    };
+    let callback = callback.borrow();
+    let callback = callback.as_ref().expect("Callback is set on construction");
+    let frame = window()
+        .request_animation_frame(callback.as_ref().unchecked_ref())
+        .expect("requestAnimationFrame failed");
+    state.frame = Some(frame);
+}
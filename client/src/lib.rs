@@ -1,7 +1,8 @@
 #![allow(dead_code, unused_variables)]
 
-use serde_json::json;
-use volv::tree::System;
+use volv::math::{Length, Vector};
+use volv::render;
+use volv::tree::{LargeBodySchema, SchemaFormat, System};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wee_alloc::WeeAlloc;
@@ -9,16 +10,49 @@ use wee_alloc::WeeAlloc;
 #[global_allocator]
 static ALLOC: WeeAlloc = WeeAlloc::INIT;
 
+mod canvas;
+mod game;
 mod handler;
-mod render;
+
+/// The width of the initial viewport in game units
+const INITIAL_VIEW_WIDTH: f32 = 5000.0;
+/// The level started by `main`
+const DEFAULT_LEVEL: &[u8] = include_bytes!("../levels/default.ron");
 
 #[wasm_bindgen]
 extern "C" {
     fn alert(message: &str);
 }
 
+/// Starts the default level on the `main-canvas` element.
+///
+/// Returns `None` if the game cannot be started.
+#[wasm_bindgen]
+pub fn main() -> Option<game::Game> {
+    start_level(DEFAULT_LEVEL, "ron")
+}
+
+/// Starts a level on the `main-canvas` element,
+/// where `extension` is the file extension of the level format, e.g. `"toml"`.
+///
+/// Returns `None` if the game cannot be started.
 #[wasm_bindgen]
-pub fn main() {
+pub fn start_level(source: &[u8], extension: &str) -> Option<game::Game> {
+    let format = match SchemaFormat::from_extension(extension) {
+        Some(format) => format,
+        None => {
+            alert(&format!("Unknown level format {:?}", extension));
+            return None;
+        }
+    };
+    let schema = match LargeBodySchema::load(source, format) {
+        Ok(schema) => schema,
+        Err(err) => {
+            alert(&format!("Cannot load level: {}", err));
+            return None;
+        }
+    };
+
     let canvas = web_sys::window()
         .unwrap()
         .document()
@@ -30,31 +64,27 @@ pub fn main() {
 
     let (width, height) = (canvas.width(), canvas.height());
 
-    let canvas = match canvas.get_context("2d").ok().flatten() {
-        Some(canvas) => canvas,
+    let renderer = match canvas::CanvasRenderer::new(canvas) {
+        Some(renderer) => renderer,
         None => {
             alert("2D canvas is not available");
-            return;
+            return None;
         }
     };
 
-    let canvas = canvas
-        .dyn_into::<web_sys::CanvasRenderingContext2d>()
-        .unwrap();
-    canvas.set_fill_style(&JsValue::from_str("black"));
-    canvas.fill_rect(0.0, 0.0, width as f64, height as f64);
-
-    let schema = json!({
-        "surface_radius": 1000.0,
-        "grav_radius": std::f64::INFINITY,
-        "mass": 1000.0,
-        "children": [
-            {
-                "surface_radius": 100.0,
-                "grav_radius": 500.0,
-                "mass": 100.0,
-            }
-        ],
-    });
-    let system = System::from_schema(serde_json::from_value(schema).unwrap(), handler::Handler);
+    let system = System::from_schema(schema, handler::Handler);
+
+    let aspect = height as f32 / width as f32;
+    let viewport = render::Viewport {
+        at: Vector::new(0.0, 0.0),
+        of: system.tree().root().id(),
+        dim: (
+            Length(INITIAL_VIEW_WIDTH),
+            Length(INITIAL_VIEW_WIDTH * aspect),
+        ),
+    };
+
+    let game = game::Game::new(system, renderer, viewport);
+    game.start();
+    Some(game)
 }
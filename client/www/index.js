@@ -1,3 +1,4 @@
 import * as wasm from "volv-client";
 
-wasm.main();
+const game = wasm.main();
+window.game = game;
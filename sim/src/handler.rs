@@ -0,0 +1,81 @@
+use std::str::FromStr;
+
+use volv::math::Length;
+use volv::tree::{BodyRef, CollisionResolution, Commands};
+
+/// How the built-in handler resolves collisions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionMode {
+    /// Collisions have no effect
+    Ignore,
+    /// Both colliding bodies are removed
+    Destroy,
+    /// The heavier body absorbs the lighter one, keeping the total area
+    Merge,
+}
+
+impl CollisionMode {
+    pub const VARIANTS: &'static [&'static str] = &["ignore", "destroy", "merge"];
+}
+
+impl FromStr for CollisionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "destroy" => Ok(Self::Destroy),
+            "merge" => Ok(Self::Merge),
+            _ => Err(format!("Unknown collision mode {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Handler {
+    mode: CollisionMode,
+}
+
+impl Handler {
+    pub fn new(mode: CollisionMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl volv::tree::Handler for Handler {
+    fn on_collision(
+        &mut self,
+        body1: BodyRef<'_>,
+        body2: BodyRef<'_>,
+        _: &mut Commands<'_>,
+    ) -> Option<(CollisionResolution, CollisionResolution)> {
+        match self.mode {
+            CollisionMode::Ignore => None,
+            CollisionMode::Destroy => {
+                Some((CollisionResolution::Remove, CollisionResolution::Remove))
+            }
+            CollisionMode::Merge => {
+                let radius = Length(body1.radius().0.hypot(body2.radius().0));
+                let merged = CollisionResolution::Merge { radius };
+                // the root body can never be absorbed
+                let body2_survives = match (is_root(body1), is_root(body2)) {
+                    (true, _) => false,
+                    (_, true) => true,
+                    _ => body2.mass() > body1.mass(),
+                };
+                if body2_survives {
+                    Some((CollisionResolution::Remove, merged))
+                } else {
+                    Some((merged, CollisionResolution::Remove))
+                }
+            }
+        }
+    }
+}
+
+fn is_root(body: BodyRef<'_>) -> bool {
+    match body {
+        BodyRef::Large(body) => body.orbit().is_none(),
+        BodyRef::Small(_) => false,
+    }
+}
@@ -0,0 +1,248 @@
+//! Runs a level headlessly and reports the executed events and the final state of the bodies.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+
+use serde::Serialize;
+use structopt::StructOpt;
+use volv::math::{Eci, Length, Mass, Time, Vector};
+use volv::render::{self, SvgRenderer, Viewport};
+use volv::tree::{
+    BodyId, BodyRef, JournalEntry, JournalEvent, LargeBodySchema, SchemaFormat, System,
+};
+
+mod handler;
+use handler::{CollisionMode, Handler};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "volv-sim", about = "Runs a volv level without a client")]
+struct Opt {
+    /// The level schema, in JSON, RON or TOML according to the file extension
+    #[structopt(parse(from_os_str))]
+    schema: PathBuf,
+    /// The time to run the system until
+    #[structopt(short, long)]
+    until: i32,
+    /// How collisions are resolved
+    #[structopt(long, default_value = "ignore", possible_values = CollisionMode::VARIANTS)]
+    collisions: CollisionMode,
+    /// The output format
+    #[structopt(long, default_value = "text", possible_values = Format::VARIANTS)]
+    format: Format,
+    /// Also draws the bodies at the end of the run to this SVG file
+    #[structopt(long, parse(from_os_str))]
+    svg: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    const VARIANTS: &'static [&'static str] = &["text", "json"];
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown format {:?}", s)),
+        }
+    }
+}
+
+/// The size of the SVG image in pixels
+const SVG_SIZE: f32 = 800.0;
+/// The width of the SVG image relative to the extent of the bodies
+const SVG_MARGIN: f32 = 1.1;
+
+/// The result of a run
+#[derive(Debug, Serialize)]
+struct Report<'t> {
+    until: Time,
+    events: &'t [JournalEntry],
+    bodies: Vec<BodyState>,
+}
+
+/// The state of a body at the end of a run
+#[derive(Debug, Serialize)]
+struct BodyState {
+    id: BodyId,
+    large: bool,
+    /// `None` for the root body
+    parent: Option<BodyId>,
+    mass: Mass,
+    /// The surface radius for large bodies
+    radius: Length,
+    /// The ECI relative to the root body
+    eci: Eci,
+    landed: bool,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    if let Err(err) = run(&opt) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run(opt: &Opt) -> Result<(), String> {
+    let format = opt
+        .schema
+        .extension()
+        .and_then(|extension| SchemaFormat::from_extension(extension.to_str()?))
+        .ok_or_else(|| format!("Unknown schema format of {}", opt.schema.display()))?;
+    let source = fs::read(&opt.schema)
+        .map_err(|err| format!("Cannot read {}: {}", opt.schema.display(), err))?;
+    let schema = LargeBodySchema::load(&source, format)
+        .map_err(|err| format!("Invalid schema in {}: {}", opt.schema.display(), err))?;
+
+    let mut system = System::from_schema(schema, Handler::new(opt.collisions));
+    system.enable_journal();
+    let until = Time(opt.until);
+    system.advance_detecting(until);
+
+    let journal = system.take_journal().expect("Journal was enabled");
+    let report = Report {
+        until,
+        events: journal.entries(),
+        bodies: body_states(&system, until),
+    };
+
+    match opt.format {
+        Format::Text => print_text(&report),
+        Format::Json => {
+            let json = serde_json::to_string_pretty(&report).expect("Report is serializable");
+            println!("{}", json);
+        }
+    }
+
+    if let Some(path) = &opt.svg {
+        fs::write(path, render_svg(&system, &report.bodies, until))
+            .map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
+
+/// Draws the whole system, centered on the root body.
+fn render_svg(system: &System<Handler>, bodies: &[BodyState], t: Time) -> String {
+    let extent = bodies
+        .iter()
+        .map(|body| body.eci.position().norm() + body.radius.0)
+        .fold(0.0, f32::max);
+    let width = Length(extent * 2.0 * SVG_MARGIN);
+    let viewport = Viewport {
+        at: Vector::new(0.0, 0.0),
+        of: system.tree().root().id(),
+        dim: (width, width),
+    };
+    let mut renderer = SvgRenderer::new(SVG_SIZE, SVG_SIZE, true);
+    render::render(system, t, &viewport, &mut renderer);
+    renderer.finish()
+}
+
+fn body_states(system: &System<Handler>, t: Time) -> Vec<BodyState> {
+    let tree = system.tree();
+    tree.dfs()
+        .map(|(body, _)| BodyState {
+            id: body.id(),
+            large: matches!(body, BodyRef::Large(_)),
+            parent: tree.parent(body.id()).map(BodyId::from),
+            mass: body.mass(),
+            radius: body.radius(),
+            eci: tree
+                .absolute_eci(body.id(), t)
+                .expect("Body is in the tree"),
+            landed: match body {
+                BodyRef::Large(_) => false,
+                BodyRef::Small(body) => body.orbit().is_none(),
+            },
+        })
+        .collect()
+}
+
+fn print_text(report: &Report<'_>) {
+    println!("Events until t={}:", report.until.0);
+    for entry in report.events {
+        println!(
+            "  t={} #{}: {}",
+            entry.time().0,
+            entry.event().0,
+            describe(entry.kind())
+        );
+        for command in entry.commands() {
+            println!("    {:?}", command);
+        }
+    }
+
+    println!("Bodies at t={}:", report.until.0);
+    for body in &report.bodies {
+        let position = body.eci.position();
+        let velocity = body.eci.velocity();
+        println!(
+            "  {} {}{}: mass {}, radius {}, position ({}, {}), velocity ({}, {}){}",
+            if body.large { "large" } else { "small" },
+            fmt_id(body.id),
+            body.parent
+                .map_or_else(String::new, |parent| format!(" in {}", fmt_id(parent))),
+            body.mass,
+            body.radius,
+            position.x,
+            position.y,
+            velocity.x,
+            velocity.y,
+            if body.landed { ", landed" } else { "" },
+        );
+    }
+}
+
+fn describe(event: &JournalEvent) -> String {
+    match event {
+        JournalEvent::Collision {
+            body1,
+            body2,
+            resolution,
+        } => match resolution {
+            Some((r1, r2)) => format!(
+                "collision of {} ({:?}) and {} ({:?})",
+                fmt_id(*body1),
+                r1,
+                fmt_id(*body2),
+                r2
+            ),
+            None => format!(
+                "collision of {} and {} (unresolved)",
+                fmt_id(*body1),
+                fmt_id(*body2)
+            ),
+        },
+        JournalEvent::FieldChange { body, from, to } => format!(
+            "{} moved from {} to {}",
+            fmt_id(*body),
+            fmt_id((*from).into()),
+            fmt_id((*to).into())
+        ),
+        JournalEvent::Timer { tag } => format!("timer {}", tag),
+        JournalEvent::Misc => "misc".to_string(),
+        JournalEvent::Skipped { bodies } => format!(
+            "skipped for removed bodies among {}",
+            bodies
+                .iter()
+                .map(|&body| fmt_id(body))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn fmt_id(id: BodyId) -> String {
+    format!("{}.{}", id.index(), id.generation())
+}
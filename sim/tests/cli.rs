@@ -0,0 +1,74 @@
+use std::process::Command;
+
+use serde_json::Value;
+
+/// Runs the level in `tests/levels` and returns the JSON report.
+fn run(level: &str, args: &[&str]) -> Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_volv-sim"))
+        .arg(format!(
+            "{}/tests/levels/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            level
+        ))
+        .args(["--format", "json"])
+        .args(args)
+        .output()
+        .expect("Cannot run volv-sim");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).expect("Invalid JSON report")
+}
+
+fn collisions(report: &Value) -> Vec<&Value> {
+    report["events"]
+        .as_array()
+        .expect("events is an array")
+        .iter()
+        .filter_map(|event| event["kind"].get("Collision"))
+        .collect()
+}
+
+fn bodies(report: &Value) -> &Vec<Value> {
+    report["bodies"].as_array().expect("bodies is an array")
+}
+
+#[test]
+fn test_collision_destroy() {
+    let report = run(
+        "collision.json",
+        &["--until", "500", "--collisions", "destroy"],
+    );
+
+    let collisions = collisions(&report);
+    assert_eq!(collisions.len(), 1);
+    assert!(collisions[0]["resolution"].is_array());
+    // the root body cannot be removed
+    let bodies = bodies(&report);
+    assert_eq!(bodies.len(), 1);
+    assert!(bodies[0]["parent"].is_null());
+}
+
+#[test]
+fn test_collision_merge() {
+    let report = run(
+        "collision.json",
+        &["--until", "500", "--collisions", "merge"],
+    );
+
+    assert_eq!(collisions(&report).len(), 1);
+    let bodies = bodies(&report);
+    assert_eq!(bodies.len(), 1);
+    assert_eq!(bodies[0]["mass"].as_f64(), Some(1001000.0));
+}
+
+#[test]
+fn test_collision_ignore() {
+    let report = run("collision.json", &["--until", "500"]);
+
+    // the planet falls through the root body without further effect
+    assert!(!collisions(&report).is_empty());
+    assert_eq!(bodies(&report).len(), 2);
+}
@@ -1,4 +1,6 @@
 #![allow(dead_code, unused_variables)]
 
+pub mod lockstep;
 pub mod math;
+pub mod render;
 pub mod tree;
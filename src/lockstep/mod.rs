@@ -0,0 +1,412 @@
+//! Lockstep synchronization of a `System` between peers.
+//!
+//! Since the orbits are analytic and the events are deterministic,
+//! peers only exchange the commands issued by players.
+//! The simulation is divided into turns of a fixed length.
+//! Each peer sends its commands for a turn, possibly none, when the turn ends locally,
+//! and a turn is only executed after the commands of all peers for it have been received.
+
+use std::collections::{BTreeMap, HashMap};
+
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::math::{Eci, Length, Mass, Time, Vector};
+use crate::tree::{BodyId, EventId, Handler, LargeBodyId, System, Tree};
+
+mod transport;
+pub use transport::*;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PeerId(pub u32);
+
+/// An action requested by a player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PlayerAction {
+    Spawn {
+        parent: LargeBodyId,
+        mass: Mass,
+        radius: Length,
+        eci: Eci,
+    },
+    Burn {
+        body: BodyId,
+        delta_v: Vector,
+    },
+    Cancel {
+        event: EventId,
+    },
+}
+
+impl PlayerAction {
+    /// Whether the action can be applied to `tree`.
+    ///
+    /// Actions from remote peers are untrusted, so invalid ones are dropped.
+    fn is_valid(&self, tree: &Tree) -> bool {
+        let finite = |vector: Vector| vector.iter().all(|x| x.is_finite());
+        match self {
+            Self::Spawn {
+                parent,
+                mass,
+                radius,
+                eci,
+            } => {
+                tree.contains((*parent).into())
+                    && mass.0 > 0.0
+                    && mass.0.is_finite()
+                    && radius.0 > 0.0
+                    && radius.0.is_finite()
+                    && finite(eci.position())
+                    && finite(eci.velocity())
+            }
+            // the root body cannot move
+            Self::Burn { body, delta_v } => tree.parent(*body).is_some() && finite(*delta_v),
+            Self::Cancel { .. } => true,
+        }
+    }
+}
+
+/// A player action scheduled at the end of a turn
+#[derive(Debug, Clone, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct PlayerCommand {
+    /// The time at which the action is applied
+    #[get_copy = "pub"]
+    time: Time,
+    #[get_copy = "pub"]
+    peer: PeerId,
+    /// The order of the command among the commands of the same peer
+    #[get_copy = "pub"]
+    seq: u32,
+    #[get = "pub"]
+    action: PlayerAction,
+}
+
+impl PlayerCommand {
+    /// The key by which commands are applied, which is the same for all peers
+    fn order(&self) -> (Time, PeerId, u32) {
+        (self.time, self.peer, self.seq)
+    }
+}
+
+/// A message sent between peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Message {
+    /// All commands of `peer` for the turn ending at `turn`
+    Turn {
+        peer: PeerId,
+        turn: Time,
+        commands: Vec<PlayerCommand>,
+    },
+}
+
+/// The reason a received message is discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageError {
+    UnknownPeer(PeerId),
+    /// The turn has already been executed
+    StaleTurn(Time),
+    /// The turn does not end on a turn boundary
+    MisalignedTurn(Time),
+}
+
+impl std::fmt::Display for MessageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnknownPeer(peer) => write!(f, "Unknown peer {}", peer.0),
+            Self::StaleTurn(turn) => write!(f, "Turn {} has already been executed", turn.0),
+            Self::MisalignedTurn(turn) => write!(f, "Turn {} is not on a turn boundary", turn.0),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+/// Runs a `System` in lockstep with other peers.
+#[derive(Debug, Getters, CopyGetters)]
+pub struct Lockstep<H: Handler, T: Transport> {
+    #[get = "pub"]
+    system: System<H>,
+    transport: T,
+    #[get_copy = "pub"]
+    peer: PeerId,
+    peers: Vec<PeerId>,
+    turn_length: i32,
+    /// The end of the first turn whose commands have not been sent
+    sending: Time,
+    /// The end of the first turn that has not been executed
+    #[get_copy = "pub"]
+    executing: Time,
+    next_seq: u32,
+    local: Vec<PlayerCommand>,
+    received: BTreeMap<Time, HashMap<PeerId, Vec<PlayerCommand>>>,
+}
+
+impl<H: Handler, T: Transport> Lockstep<H, T> {
+    /// Starts a session from `system`, which must be in the same state for all peers.
+    ///
+    /// `peers` contains all peers in the session, including `peer`.
+    pub fn new(
+        system: System<H>,
+        transport: T,
+        peer: PeerId,
+        peers: Vec<PeerId>,
+        turn_length: i32,
+    ) -> Self {
+        assert!(turn_length > 0, "Turn length must be positive");
+        assert!(
+            peers.contains(&peer),
+            "The local peer must be in the session"
+        );
+        let first = Time(system.now().0 + turn_length);
+        Self {
+            system,
+            transport,
+            peer,
+            peers,
+            turn_length,
+            sending: first,
+            executing: first,
+            next_seq: 0,
+            local: Vec::new(),
+            received: BTreeMap::new(),
+        }
+    }
+
+    /// Queues an action to be applied at the end of the current turn.
+    pub fn submit(&mut self, action: PlayerAction) -> Time {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.local.push(PlayerCommand {
+            time: self.sending,
+            peer: self.peer,
+            seq,
+            action,
+        });
+        self.sending
+    }
+
+    /// Sends the commands of the current turn and starts the next turn.
+    pub fn end_turn(&mut self) {
+        let commands = std::mem::take(&mut self.local);
+        let message = Message::Turn {
+            peer: self.peer,
+            turn: self.sending,
+            commands,
+        };
+        self.transport.send(&message);
+        self.receive(message).expect("Local turns are valid");
+        self.sending = Time(self.sending.0 + self.turn_length);
+    }
+
+    /// Receives pending messages and executes all turns with commands from every peer.
+    ///
+    /// Returns the time up to which the system has been advanced.
+    pub fn poll(&mut self) -> Time {
+        while let Some(message) = self.transport.recv() {
+            // invalid messages would never be removed, so they are discarded
+            let _ = self.receive(message);
+        }
+
+        while let Some(turn) = self.received.get(&self.executing) {
+            if !self.peers.iter().all(|peer| turn.contains_key(peer)) {
+                break;
+            }
+            let turn = self
+                .received
+                .remove(&self.executing)
+                .expect("Turn was obtained in map");
+            let mut commands = turn.into_values().flatten().collect::<Vec<_>>();
+            commands.sort_by_key(PlayerCommand::order);
+
+            self.system.advance_event(self.executing);
+            for command in commands {
+                self.apply(command);
+            }
+            self.executing = Time(self.executing.0 + self.turn_length);
+        }
+
+        Time(self.executing.0 - self.turn_length)
+    }
+
+    fn receive(&mut self, message: Message) -> Result<(), MessageError> {
+        match message {
+            Message::Turn {
+                peer,
+                turn,
+                mut commands,
+            } => {
+                if !self.peers.contains(&peer) {
+                    return Err(MessageError::UnknownPeer(peer));
+                }
+                if turn < self.executing {
+                    return Err(MessageError::StaleTurn(turn));
+                }
+                let offset = turn
+                    .0
+                    .checked_sub(self.executing.0)
+                    .ok_or(MessageError::MisalignedTurn(turn))?;
+                if offset % self.turn_length != 0 {
+                    return Err(MessageError::MisalignedTurn(turn));
+                }
+
+                commands.retain(|command| command.peer == peer && command.time == turn);
+                self.received
+                    .entry(turn)
+                    .or_default()
+                    .entry(peer)
+                    .or_insert(commands);
+                Ok(())
+            }
+        }
+    }
+
+    fn apply(&mut self, command: PlayerCommand) {
+        let action = command.action;
+        if !action.is_valid(self.system.tree()) {
+            return;
+        }
+        self.system.issue(move |_, commands| match action {
+            PlayerAction::Spawn {
+                parent,
+                mass,
+                radius,
+                eci,
+            } => {
+                commands.spawn(parent, mass, radius, eci);
+            }
+            PlayerAction::Burn { body, delta_v } => commands.burn(body, delta_v),
+            PlayerAction::Cancel { event } => commands.cancel(event),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::LargeBodySchema;
+
+    fn system() -> System<()> {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "grav_radius": 1e30,
+            "mass": 1e6,
+            "children": [{
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+            }],
+        }))
+        .expect("Invalid schema");
+        System::from_schema(schema, ())
+    }
+
+    #[test]
+    fn test_loopback() {
+        let peers = vec![PeerId(0), PeerId(1)];
+        let mut transports = Loopback::network(2);
+        let mut b = Lockstep::new(
+            system(),
+            transports.pop().unwrap(),
+            PeerId(1),
+            peers.clone(),
+            10,
+        );
+        let mut a = Lockstep::new(system(), transports.pop().unwrap(), PeerId(0), peers, 10);
+        let root = a.system().tree().root().id();
+
+        a.submit(PlayerAction::Spawn {
+            parent: root,
+            mass: Mass(1.0),
+            radius: Length(1.0),
+            eci: Eci::new(Vector::new(500.0, 0.0), Vector::new(0.0, 44.0)),
+        });
+        b.submit(PlayerAction::Spawn {
+            parent: root,
+            mass: Mass(2.0),
+            radius: Length(1.0),
+            eci: Eci::new(Vector::new(-500.0, 0.0), Vector::new(0.0, -44.0)),
+        });
+        a.end_turn();
+        assert_eq!(a.poll(), Time(0), "Turn executed without commands from b");
+        b.end_turn();
+        assert_eq!(b.poll(), Time(10));
+        assert_eq!(a.poll(), Time(10));
+        assert_eq!(a.system().checksum(), b.system().checksum());
+
+        let body = a.system().tree().small_bodies().next().unwrap().0.id();
+        b.submit(PlayerAction::Burn {
+            body,
+            delta_v: Vector::new(1.0, 2.0),
+        });
+        b.end_turn();
+        a.end_turn();
+        a.end_turn();
+        b.end_turn();
+        assert_eq!(a.poll(), Time(30));
+        assert_eq!(b.poll(), Time(30));
+        assert_eq!(a.system().checksum(), b.system().checksum());
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        let peers = vec![PeerId(0), PeerId(1)];
+        let mut transports = Loopback::network(3);
+        let mut rogue = transports.pop().unwrap();
+        let mut b = Lockstep::new(
+            system(),
+            transports.pop().unwrap(),
+            PeerId(1),
+            peers.clone(),
+            10,
+        );
+        let mut a = Lockstep::new(system(), transports.pop().unwrap(), PeerId(0), peers, 10);
+        let root = a.system().tree().root().id();
+
+        // a burn of the root body is dropped instead of panicking
+        b.submit(PlayerAction::Burn {
+            body: root.into(),
+            delta_v: Vector::new(1.0, 0.0),
+        });
+        b.submit(PlayerAction::Spawn {
+            parent: root,
+            mass: Mass(-1.0),
+            radius: Length(1.0),
+            eci: Eci::new(Vector::new(500.0, 0.0), Vector::new(0.0, 44.0)),
+        });
+        a.end_turn();
+        b.end_turn();
+        assert_eq!(a.poll(), Time(10));
+        assert_eq!(b.poll(), Time(10));
+        assert_eq!(a.system().checksum(), b.system().checksum());
+        assert_eq!(a.system().tree().small_bodies().count(), 0);
+
+        // messages from unknown peers and for executed or misaligned turns are discarded
+        let invalid = [
+            (PeerId(2), Time(20), MessageError::UnknownPeer(PeerId(2))),
+            (PeerId(1), Time(10), MessageError::StaleTurn(Time(10))),
+            (
+                PeerId(1),
+                Time(i32::MIN),
+                MessageError::StaleTurn(Time(i32::MIN)),
+            ),
+            (PeerId(1), Time(25), MessageError::MisalignedTurn(Time(25))),
+            (
+                PeerId(1),
+                Time(i32::MAX),
+                MessageError::MisalignedTurn(Time(i32::MAX)),
+            ),
+        ];
+        for &(peer, turn, err) in &invalid {
+            let message = Message::Turn {
+                peer,
+                turn,
+                commands: Vec::new(),
+            };
+            rogue.send(&message);
+            assert_eq!(a.receive(message), Err(err));
+        }
+        assert_eq!(a.poll(), Time(10));
+        assert!(a.received.is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+use std::f64::consts::PI;
+
+use getset::*;
+
+use super::*;
+
+/// Convergence tolerance of the Householder iterations
+const TOLERANCE: f64 = 1e-11;
+/// The maximum number of Householder or Halley iterations
+const MAX_ITERATIONS: usize = 20;
+
+/// A transfer orbit found by `lambert`
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct LambertSolution {
+    /// The ECI at the departure point, relative to the parent body
+    #[get = "pub"]
+    departure: Eci,
+    /// The ECI at the arrival point, relative to the parent body
+    #[get = "pub"]
+    arrival: Eci,
+    /// The number of complete revolutions before arrival
+    #[get_copy = "pub"]
+    revolutions: u32,
+    /// Whether the transfer sweeps more than half a revolution, excluding complete revolutions
+    #[get_copy = "pub"]
+    long_way: bool,
+}
+
+/// Solves Lambert's problem: finds the orbits around a parent body of mass `mass` that travel
+/// from `from` to `to` in `time_of_flight`.
+///
+/// Both short-way and long-way solutions are returned.
+/// Transfers with up to `max_revolutions` complete revolutions are also returned if the time of
+/// flight allows them, with two solutions for each number of revolutions.
+///
+/// The departure ECI can be passed to `Orbit::from_mpv` with the departure time as the epoch.
+pub fn lambert(
+    mass: Mass,
+    from: Vector,
+    to: Vector,
+    time_of_flight: f64,
+    max_revolutions: u32,
+) -> Vec<LambertSolution> {
+    let mu = GRAVITY * f64::from(mass.0);
+    let (r1, r2) = (to_f64(from), to_f64(to));
+    if time_of_flight <= 0.0 || r1 == r2 {
+        return Vec::new();
+    }
+
+    let mut solutions = solve(mu, r1, r2, time_of_flight, max_revolutions, true);
+    solutions.extend(solve(mu, r1, r2, time_of_flight, max_revolutions, false));
+    solutions
+}
+
+/// Izzo's algorithm for a transfer in one direction
+fn solve(
+    mu: f64,
+    r1: DVector,
+    r2: DVector,
+    time_of_flight: f64,
+    max_revolutions: u32,
+    anticlockwise: bool,
+) -> Vec<LambertSolution> {
+    let (r1_norm, r2_norm) = (r1.norm(), r2.norm());
+    let c = (r2 - r1).norm();
+    let s = (r1_norm + r2_norm + c) / 2.0;
+
+    let direction = if anticlockwise { 1.0 } else { -1.0 };
+    let cross = r1.x * r2.y - r1.y * r2.x;
+    let angle = (direction * cross).atan2(r1.dot(&r2)).rem_euclid(2.0 * PI);
+    let long_way = angle > PI;
+
+    let mut lambda = (1.0 - c / s).max(0.0).sqrt();
+    if long_way {
+        lambda = -lambda;
+    }
+
+    let (ir1, ir2) = (r1 / r1_norm, r2 / r2_norm);
+    let it1 = DVector::new(-ir1.y, ir1.x) * direction;
+    let it2 = DVector::new(-ir2.y, ir2.x) * direction;
+
+    let t = (2.0 * mu / s.powi(3)).sqrt() * time_of_flight;
+
+    let gamma = (mu * s / 2.0).sqrt();
+    let rho = (r1_norm - r2_norm) / c;
+    let sigma = (1.0 - rho * rho).max(0.0).sqrt();
+
+    find_xs(lambda, t, max_revolutions)
+        .into_iter()
+        .map(|(x, revolutions)| {
+            let y = (1.0 - lambda * lambda + lambda * lambda * x * x).sqrt();
+            let vr1 = gamma * ((lambda * y - x) - rho * (lambda * y + x)) / r1_norm;
+            let vr2 = -gamma * ((lambda * y - x) + rho * (lambda * y + x)) / r2_norm;
+            let vt = gamma * sigma * (y + lambda * x);
+            let v1 = ir1 * vr1 + it1 * (vt / r1_norm);
+            let v2 = ir2 * vr2 + it2 * (vt / r2_norm);
+
+            LambertSolution {
+                departure: Eci::new(to_f32(r1), to_f32(v1)),
+                arrival: Eci::new(to_f32(r2), to_f32(v2)),
+                revolutions,
+                long_way,
+            }
+        })
+        .collect()
+}
+
+/// Finds the values of Izzo's `x` variable for the nondimensional time of flight `t`,
+/// paired with the number of revolutions of each solution
+fn find_xs(lambda: f64, t: f64, max_revolutions: u32) -> Vec<(f64, u32)> {
+    let mut m_max = ((t / PI).floor() as u32).min(max_revolutions);
+    let t00 = lambda.acos() + lambda * (1.0 - lambda * lambda).sqrt();
+    let t0 = t00 + f64::from(m_max) * PI;
+    let t1 = 2.0 / 3.0 * (1.0 - lambda.powi(3));
+
+    if t < t0 && m_max > 0 {
+        // find the minimum time of flight with m_max revolutions by Halley iterations
+        let mut x_old = 0.0;
+        let mut t_min = t0;
+        for _ in 0..MAX_ITERATIONS {
+            let (dt, ddt, dddt) = tof_derivatives(x_old, t_min, lambda);
+            if dt == 0.0 {
+                break;
+            }
+            let x_new = x_old - dt * ddt / (ddt * ddt - dt * dddt / 2.0);
+            if (x_old - x_new).abs() < 1e-13 {
+                break;
+            }
+            t_min = x_to_tof(x_new, m_max, lambda);
+            x_old = x_new;
+        }
+        if t_min > t {
+            m_max -= 1;
+        }
+    }
+
+    let x0 = if t >= t00 {
+        -(t - t00) / (t - t00 + 4.0)
+    } else if t <= t1 {
+        t1 * (t1 - t) / (2.0 / 5.0 * (1.0 - lambda.powi(5)) * t) + 1.0
+    } else {
+        (t / t00).powf(2f64.ln() / (t1 / t00).ln()) - 1.0
+    };
+
+    let mut xs = vec![(householder(t, x0, 0, lambda), 0)];
+    for m in 1..=m_max {
+        let revs = f64::from(m) * PI;
+        let left = ((revs + PI) / (8.0 * t)).powf(2.0 / 3.0);
+        xs.push((householder(t, (left - 1.0) / (left + 1.0), m, lambda), m));
+        let right = (8.0 * t / revs).powf(2.0 / 3.0);
+        xs.push((householder(t, (right - 1.0) / (right + 1.0), m, lambda), m));
+    }
+    xs
+}
+
+fn householder(t: f64, mut x0: f64, revolutions: u32, lambda: f64) -> f64 {
+    for _ in 0..MAX_ITERATIONS {
+        let tof = x_to_tof(x0, revolutions, lambda);
+        let (dt, ddt, dddt) = tof_derivatives(x0, tof, lambda);
+        let delta = tof - t;
+        let dt2 = dt * dt;
+        let x = x0
+            - delta * (dt2 - delta * ddt / 2.0)
+                / (dt * (dt2 - delta * ddt) + dddt * delta * delta / 6.0);
+        if (x - x0).abs() < TOLERANCE {
+            return x;
+        }
+        x0 = x;
+    }
+    x0
+}
+
+fn tof_derivatives(x: f64, t: f64, lambda: f64) -> (f64, f64, f64) {
+    let l2 = lambda * lambda;
+    let l3 = l2 * lambda;
+    let umx2 = 1.0 - x * x;
+    let y = (1.0 - l2 * umx2).sqrt();
+    let dt = (3.0 * t * x - 2.0 + 2.0 * l3 * x / y) / umx2;
+    let ddt = (3.0 * t + 5.0 * x * dt + 2.0 * (1.0 - l2) * l3 / y.powi(3)) / umx2;
+    let dddt = (7.0 * x * ddt + 8.0 * dt - 6.0 * (1.0 - l2) * l2 * l3 * x / y.powi(5)) / umx2;
+    (dt, ddt, dddt)
+}
+
+/// The nondimensional time of flight for the given `x`
+fn x_to_tof(x: f64, revolutions: u32, lambda: f64) -> f64 {
+    const BATTIN: f64 = 0.01;
+    const LAGRANGE: f64 = 0.2;
+
+    let revs = f64::from(revolutions) * PI;
+    let dist = (x - 1.0).abs();
+    if dist < LAGRANGE && dist > BATTIN {
+        return x_to_tof_lagrange(x, revs, lambda);
+    }
+
+    let k = lambda * lambda;
+    let e = x * x - 1.0;
+    let rho = e.abs();
+    let z = (1.0 + k * e).sqrt();
+    if dist < BATTIN {
+        let eta = z - lambda * x;
+        let s1 = 0.5 * (1.0 - lambda - x * eta);
+        let q = hypergeometric(s1) * 4.0 / 3.0;
+        (eta.powi(3) * q + 4.0 * lambda * eta) / 2.0 + revs / rho.powf(1.5)
+    } else {
+        let y = rho.sqrt();
+        let g = x * z - lambda * e;
+        let d = if e < 0.0 {
+            revs + g.acos()
+        } else {
+            (y * (z - lambda * x) + g).ln()
+        };
+        (x - lambda * z - d / y) / e
+    }
+}
+
+fn x_to_tof_lagrange(x: f64, revs: f64, lambda: f64) -> f64 {
+    let a = 1.0 / (1.0 - x * x);
+    if a > 0.0 {
+        let alpha = 2.0 * x.acos();
+        let beta = 2.0 * (lambda * lambda / a).sqrt().asin().copysign(lambda);
+        a * a.sqrt() * ((alpha - alpha.sin()) - (beta - beta.sin()) + 2.0 * revs) / 2.0
+    } else {
+        let alpha = 2.0 * x.acosh();
+        let beta = 2.0 * (-lambda * lambda / a).sqrt().asinh().copysign(lambda);
+        -a * (-a).sqrt() * ((beta - beta.sinh()) - (alpha - alpha.sinh())) / 2.0
+    }
+}
+
+/// The hypergeometric function 2F1(3, 1, 5/2, z) used in Battin's series
+fn hypergeometric(z: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term: f64 = 1.0;
+    let mut j = 0.0;
+    while term.abs() > TOLERANCE {
+        term *= (3.0 + j) * (1.0 + j) / (2.5 + j) * z / (j + 1.0);
+        sum += term;
+        j += 1.0;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASS: Mass = Mass(1e6);
+
+    /// Propagates each departure and checks that it reaches `to` after `time_of_flight`.
+    fn assert_arrivals(
+        solutions: &[LambertSolution],
+        from: Vector,
+        to: Vector,
+        time_of_flight: i32,
+    ) {
+        for solution in solutions {
+            assert_eq!(solution.departure().position(), from);
+            let orbit = Orbit::from_mpv(MASS, solution.departure().clone(), Time(0));
+            let eci = orbit.eci(Time(time_of_flight));
+            let error = (eci.position() - to).norm();
+            assert!(
+                error < to.norm() * 1e-3,
+                "{:?} misses by {}",
+                solution,
+                error
+            );
+            let error = (eci.velocity() - solution.arrival().velocity()).norm();
+            assert!(
+                error < solution.arrival().velocity().norm() * 1e-3,
+                "{:?} arrives at {:?}",
+                solution,
+                eci.velocity()
+            );
+        }
+    }
+
+    #[test]
+    fn test_single_revolution() {
+        let (from, to) = (Vector::new(2000.0, 0.0), Vector::new(-1000.0, 2500.0));
+        let solutions = lambert(MASS, from, to, 300.0, 0);
+        assert_eq!(solutions.len(), 2);
+        assert_ne!(solutions[0].long_way(), solutions[1].long_way());
+        assert!(solutions.iter().all(|solution| solution.revolutions() == 0));
+        assert_arrivals(&solutions, from, to, 300);
+
+        // a hyperbolic transfer
+        let solutions = lambert(MASS, from, to, 40.0, 0);
+        assert_eq!(solutions.len(), 2);
+        assert_arrivals(&solutions, from, to, 40);
+    }
+
+    #[test]
+    fn test_multi_revolution() {
+        // orbits between these radii have periods of roughly 800
+        let (from, to) = (Vector::new(2000.0, 0.0), Vector::new(0.0, -3000.0));
+        let solutions = lambert(MASS, from, to, 2000.0, 2);
+        for revolutions in 1..=2 {
+            let count = solutions
+                .iter()
+                .filter(|solution| solution.revolutions() == revolutions)
+                .count();
+            assert_eq!(count, 4, "{} revolutions", revolutions);
+        }
+        assert_arrivals(&solutions, from, to, 2000);
+
+        assert!(lambert(MASS, from, to, 0.0, 2).is_empty());
+        assert!(lambert(MASS, from, from, 100.0, 2).is_empty());
+    }
+}
@@ -2,10 +2,29 @@ use std::fmt;
 
 use derive_more::{Deref, DerefMut, Mul};
 
+mod lambert;
+pub use lambert::*;
+
 mod orbit;
 pub use orbit::*;
 
+mod transfer;
+pub use transfer::*;
+
 pub type Vector = nalgebra::Vector2<f32>;
+/// Double-precision vector for intermediate orbital calculations
+pub(crate) type DVector = nalgebra::Vector2<f64>;
+
+/// The gravitational constant in game units
+pub const GRAVITY: f64 = 1.0;
+
+pub(crate) fn to_f64(vector: Vector) -> DVector {
+    DVector::new(f64::from(vector.x), f64::from(vector.y))
+}
+
+pub(crate) fn to_f32(vector: DVector) -> Vector {
+    Vector::new(vector.x as f32, vector.y as f32)
+}
 
 macro_rules! unit {
     ($name:ident, $unit:literal, $base:literal) => {
@@ -20,6 +39,7 @@ macro_rules! unit {
             serde::Serialize,
             serde::Deserialize,
         )]
+        #[serde(transparent)]
         pub struct $name(pub f32);
 
         impl fmt::Display for $name {
@@ -53,7 +73,9 @@ fn to_sci(mut value: f32) -> (f32, &'static str) {
 unit!(Length, "m", 1.0);
 unit!(Mass, "g", 1000.0);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(
+    Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 pub struct Time(pub i32);
 
 #[derive(Debug, Clone, Copy, Mul)]
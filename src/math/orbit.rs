@@ -1,46 +1,369 @@
+use std::f64::consts::PI;
+use std::hash::{Hash, Hasher};
+
+use derive_more::{Add, Neg, Sub};
 use getset::*;
 
 use super::*;
 
-#[derive(Debug)]
-pub struct Orbit {}
+/// Eccentricities within this distance from 1 are treated as parabolic
+const PARABOLIC_TOLERANCE: f64 = 1e-6;
+/// The maximum number of iterations when solving Kepler's equation
+const KEPLER_ITERATIONS: usize = 50;
+/// The maximum recursion depth when subdividing a segment of an orbit path
+const PATH_MAX_DEPTH: u32 = 12;
+/// The minimum semi-latus rectum as a fraction of the distance from the parent body.
+///
+/// Trajectories with less angular momentum are widened to this,
+/// since the elements of a radial trajectory are degenerate.
+/// For a body at rest, this is `1 - e`, which must exceed `PARABOLIC_TOLERANCE`.
+const MIN_SEMI_LATUS_RECTUM: f64 = 1e-5;
+/// Eccentricities below this are treated as circular when solving for an anomaly
+const CIRCULAR_TOLERANCE: f64 = 1e-9;
+
+/// A Keplerian orbit around a parent body, located at the origin.
+///
+/// Radial trajectories (zero angular momentum) are approximated by very narrow orbits.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Orbit {
+    /// Standard gravitational parameter of the parent body
+    mu: f64,
+    /// Semi-latus rectum
+    p: f64,
+    e: f64,
+    /// Angle of the periapsis, anticlockwise from the x-axis
+    arg_periapsis: f64,
+    /// 1 for anticlockwise orbits, -1 for clockwise orbits
+    direction: f64,
+    /// Mean anomaly at `epoch`
+    mean_anomaly: f64,
+    epoch: Time,
+}
+
+// hashes the exact bits of the elements, for detecting divergence between simulations
+impl Hash for Orbit {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        for &element in &[
+            self.mu,
+            self.p,
+            self.e,
+            self.arg_periapsis,
+            self.direction,
+            self.mean_anomaly,
+        ] {
+            element.to_bits().hash(state);
+        }
+        self.epoch.hash(state);
+    }
+}
 
 impl Orbit {
-    /// Calculate an orbit from the mass of the sun and the current ECI position+velocity of the
-    /// planet
-    pub fn from_mpv(mass: Mass, eci: Eci) -> Self {
-        unimplemented!()
+    /// Calculate an orbit from the mass of the sun and the ECI position+velocity of the planet
+    /// at `epoch`
+    ///
+    /// If the angular momentum is (almost) zero, e.g. for a body at rest relative to its parent,
+    /// a tiny tangential velocity is added so that the orbit is a narrow ellipse or hyperbola
+    /// instead of a degenerate line.
+    /// A position at the origin is moved slightly along the x-axis for the same reason.
+    pub fn from_mpv(mass: Mass, eci: Eci, epoch: Time) -> Self {
+        let mu = GRAVITY * f64::from(mass.0);
+        let mut r = to_f64(eci.position());
+        let mut v = to_f64(eci.velocity());
+
+        if r.norm_squared() == 0.0 {
+            r = DVector::new(f64::from(f32::EPSILON), 0.0);
+        }
+        let mut h = r.x * v.y - r.y * v.x;
+        let min_h = (mu * r.norm() * MIN_SEMI_LATUS_RECTUM).sqrt();
+        if h.abs() < min_h {
+            let target = min_h.copysign(h);
+            let tangent = DVector::new(-r.y, r.x) / r.norm_squared();
+            v += tangent * (target - h);
+            h = target;
+        }
+
+        let e_vec = ((v.norm_squared() - mu / r.norm()) * r - r.dot(&v) * v) / mu;
+        let e = e_vec.norm();
+        let arg_periapsis = if e > 0.0 { e_vec.y.atan2(e_vec.x) } else { 0.0 };
+        let direction = if h < 0.0 { -1.0 } else { 1.0 };
+
+        let mut orbit = Self {
+            mu,
+            p: h * h / mu,
+            e,
+            arg_periapsis,
+            direction,
+            mean_anomaly: 0.0,
+            epoch,
+        };
+        let true_anomaly = normalize_angle(direction * (r.y.atan2(r.x) - arg_periapsis));
+        orbit.mean_anomaly = orbit.to_mean_anomaly(true_anomaly);
+        orbit
     }
 
     pub fn eccentricity(&self) -> f64 {
-        unimplemented!()
+        self.e
     }
+
+    /// Returns the semi-major axis, which is negative for hyperbolic orbits and infinite for
+    /// parabolic orbits.
+    pub fn semi_major_axis(&self) -> f64 {
+        self.p / (1.0 - self.e * self.e)
+    }
+
+    /// Whether the body moves anticlockwise around its parent
+    pub fn is_anticlockwise(&self) -> bool {
+        self.direction > 0.0
+    }
+
+    /// Returns the time for a full revolution, or `None` for open orbits.
+    pub fn period(&self) -> Option<f64> {
+        if self.is_closed() {
+            Some(2.0 * PI / self.mean_motion())
+        } else {
+            None
+        }
+    }
+
     pub fn peripapsis(&self) -> Vector {
-        unimplemented!()
+        to_f32(self.position_at(0.0))
     }
-    pub fn apoapsis(&self) -> Vector {
-        unimplemented!()
+
+    /// Returns the apoapsis, or `None` for open orbits.
+    pub fn apoapsis(&self) -> Option<Vector> {
+        if self.is_closed() {
+            Some(to_f32(self.position_at(PI)))
+        } else {
+            None
+        }
     }
+
+    /// Returns the mean anomaly at the epoch of the orbit.
+    ///
+    /// For parabolic orbits, this is the value of Barker's equation instead.
     pub fn anomaly(&self) -> f64 {
-        unimplemented!()
+        self.mean_anomaly
     }
 
     pub fn position(&self, t: Time) -> Vector {
-        unimplemented!()
+        to_f32(self.position_at(self.true_anomaly(t)))
     }
     pub fn velocity(&self, t: Time) -> Vector {
-        unimplemented!()
+        to_f32(self.velocity_at(self.true_anomaly(t)))
     }
     pub fn eci(&self, t: Time) -> Eci {
-        Eci::new(self.position(t), self.velocity(t))
+        let anomaly = self.true_anomaly(t);
+        Eci::new(
+            to_f32(self.position_at(anomaly)),
+            to_f32(self.velocity_at(anomaly)),
+        )
+    }
+
+    /// Returns the earliest time not before `since` at which the distance from the parent body
+    /// is `height`.
+    ///
+    /// A circular orbit is always at the distance of its radius,
+    /// so `since` is returned if `height` is the radius.
+    pub fn time_reaching(&self, height: Length, since: Time) -> Option<Time> {
+        let height = f64::from(height.0);
+        let periapsis = self.p / (1.0 + self.e);
+        // allow for the rounding of a height computed from an f32 position
+        let tolerance = height * f64::from(f32::EPSILON);
+        if height < periapsis - tolerance {
+            return None;
+        }
+        if self.is_closed() && height > self.p / (1.0 - self.e) + tolerance {
+            return None;
+        }
+        if self.e < CIRCULAR_TOLERANCE {
+            return Some(since);
+        }
+
+        let cos = ((self.p / height - 1.0) / self.e).clamp(-1.0, 1.0);
+        let anomaly = cos.acos();
+
+        let since_mean = self.mean_anomaly_at(since);
+        let mut delay: Option<f64> = None;
+        for &anomaly in &[-anomaly, anomaly] {
+            let mut delta = self.to_mean_anomaly(anomaly) - since_mean;
+            if self.is_closed() {
+                delta = delta.rem_euclid(2.0 * PI);
+            }
+            if delta >= 0.0 {
+                delay = Some(delay.map_or(delta, |delay| delay.min(delta)));
+            }
+        }
+        let delay = delay? / self.mean_motion();
+        Some(Time(since.0 + delay.ceil() as i32))
+    }
+
+    /// Approximates the path of the orbit with a polyline in the frame of the parent body.
+    ///
+    /// No point on the orbit deviates from the polyline by more than `max_error`,
+    /// so the point density adapts to the curvature of the path.
+    /// Only the part within `clip_radius` from the parent body is sampled,
+    /// which is typically the `grav_radius` of the parent body.
+    ///
+    /// For closed orbits within `clip_radius`, the first point is repeated at the end.
+    pub fn path(&self, max_error: Length, clip_radius: Length) -> Vec<Vector> {
+        let clip_radius = f64::from(clip_radius.0);
+        let range = if self.is_closed() && self.p / (1.0 - self.e) <= clip_radius {
+            PI
+        } else {
+            let cos = (self.p / clip_radius - 1.0) / self.e;
+            if cos > 1.0 {
+                return Vec::new(); // the whole orbit is beyond the clip radius
+            }
+            let mut range = cos.max(-1.0).acos();
+            if !self.is_closed() {
+                // an infinite clip radius would reach the asymptote
+                let asymptote = (-1.0 / self.e).max(-1.0).acos();
+                range = range.min(asymptote * (1.0 - 1e-3));
+            }
+            range
+        };
+
+        let tolerance = f64::from(max_error.0);
+        let start = (-range, self.position_at(-range));
+        let mut path = vec![to_f32(start.1)];
+        let mut from = start;
+        // start with several segments, otherwise a symmetric arc may have its midpoint on the chord
+        for i in 1..=4 {
+            let anomaly = -range + range * 0.5 * f64::from(i);
+            let to = (anomaly, self.position_at(anomaly));
+            self.subdivide(from, to, tolerance, 0, &mut path);
+            from = to;
+        }
+        path
+    }
+
+    fn subdivide(
+        &self,
+        (from_anomaly, from): (f64, DVector),
+        (to_anomaly, to): (f64, DVector),
+        tolerance: f64,
+        depth: u32,
+        path: &mut Vec<Vector>,
+    ) {
+        let mid_anomaly = (from_anomaly + to_anomaly) * 0.5;
+        let mid = self.position_at(mid_anomaly);
+        if depth < PATH_MAX_DEPTH && segment_distance(mid, from, to) > tolerance {
+            let mid = (mid_anomaly, mid);
+            self.subdivide((from_anomaly, from), mid, tolerance, depth + 1, path);
+            self.subdivide(mid, (to_anomaly, to), tolerance, depth + 1, path);
+        } else {
+            path.push(to_f32(to));
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.e < 1.0 - PARABOLIC_TOLERANCE
+    }
+
+    fn is_parabolic(&self) -> bool {
+        (self.e - 1.0).abs() <= PARABOLIC_TOLERANCE
+    }
+
+    /// The rate of change of the mean anomaly
+    fn mean_motion(&self) -> f64 {
+        if self.is_parabolic() {
+            2.0 * (self.mu / self.p.powi(3)).sqrt()
+        } else {
+            (self.mu / self.semi_major_axis().abs().powi(3)).sqrt()
+        }
+    }
+
+    fn mean_anomaly_at(&self, t: Time) -> f64 {
+        let dt = f64::from(t.0) - f64::from(self.epoch.0);
+        let mean_anomaly = self.mean_anomaly + self.mean_motion() * dt;
+        if self.is_closed() {
+            normalize_angle(mean_anomaly)
+        } else {
+            mean_anomaly
+        }
+    }
+
+    /// Returns the true anomaly at time `t`.
+    pub fn true_anomaly(&self, t: Time) -> f64 {
+        let mean = self.mean_anomaly_at(t);
+        let e = self.e;
+        if self.is_parabolic() {
+            let b = 1.5 * mean;
+            let w = (b + (b * b + 1.0).sqrt()).cbrt();
+            2.0 * (w - w.recip()).atan()
+        } else if self.is_closed() {
+            let mut ecc = if e > 0.8 { PI.copysign(mean) } else { mean };
+            for _ in 0..KEPLER_ITERATIONS {
+                let delta = (ecc - e * ecc.sin() - mean) / (1.0 - e * ecc.cos());
+                ecc -= delta;
+                if delta.abs() < 1e-12 {
+                    break;
+                }
+            }
+            2.0 * ((1.0 + e).sqrt() * (ecc * 0.5).sin()).atan2((1.0 - e).sqrt() * (ecc * 0.5).cos())
+        } else {
+            let mut hyp = (mean / e).asinh();
+            for _ in 0..KEPLER_ITERATIONS {
+                let delta = (e * hyp.sinh() - hyp - mean) / (e * hyp.cosh() - 1.0);
+                hyp -= delta;
+                if delta.abs() < 1e-12 {
+                    break;
+                }
+            }
+            2.0 * (((e + 1.0) / (e - 1.0)).sqrt() * (hyp * 0.5).tanh()).atan()
+        }
+    }
+
+    fn to_mean_anomaly(&self, true_anomaly: f64) -> f64 {
+        let e = self.e;
+        if self.is_parabolic() {
+            let d = (true_anomaly * 0.5).tan();
+            d + d.powi(3) / 3.0
+        } else if self.is_closed() {
+            let ecc = 2.0
+                * ((1.0 - e).sqrt() * (true_anomaly * 0.5).sin())
+                    .atan2((1.0 + e).sqrt() * (true_anomaly * 0.5).cos());
+            ecc - e * ecc.sin()
+        } else {
+            let hyp = 2.0 * (((e - 1.0) / (e + 1.0)).sqrt() * (true_anomaly * 0.5).tan()).atanh();
+            e * hyp.sinh() - hyp
+        }
+    }
+
+    fn position_at(&self, true_anomaly: f64) -> DVector {
+        let r = self.p / (1.0 + self.e * true_anomaly.cos());
+        let angle = self.arg_periapsis + self.direction * true_anomaly;
+        DVector::new(angle.cos(), angle.sin()) * r
+    }
+
+    fn velocity_at(&self, true_anomaly: f64) -> DVector {
+        let k = (self.mu / self.p).sqrt();
+        let radial = k * self.e * true_anomaly.sin();
+        let tangential = k * (1.0 + self.e * true_anomaly.cos()) * self.direction;
+        let angle = self.arg_periapsis + self.direction * true_anomaly;
+        let (sin, cos) = angle.sin_cos();
+        DVector::new(cos, sin) * radial + DVector::new(-sin, cos) * tangential
     }
+}
+
+/// Normalizes an angle into the range [-pi, pi)
+fn normalize_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
 
-    pub fn time_reaching(&self, height: Length) -> Option<Time> {
-        unimplemented!()
+/// Distance from `point` to the line segment between `from` and `to`
+fn segment_distance(point: DVector, from: DVector, to: DVector) -> f64 {
+    let line = to - from;
+    let len2 = line.norm_squared();
+    if len2 == 0.0 {
+        return (point - from).norm();
     }
+    let ratio = ((point - from).dot(&line) / len2).clamp(0.0, 1.0);
+    (point - (from + line * ratio)).norm()
 }
 
-#[derive(Debug, Clone, CopyGetters, serde::Serialize, serde::Deserialize)]
+/// The position and velocity of a body relative to a reference body
+#[derive(Debug, Clone, Add, Sub, Neg, CopyGetters, serde::Serialize, serde::Deserialize)]
 pub struct Eci {
     #[get_copy = "pub"]
     position: Vector,
@@ -52,4 +375,172 @@ impl Eci {
     pub fn new(position: Vector, velocity: Vector) -> Self {
         Self { position, velocity }
     }
+
+    /// The ECI of the reference body itself
+    pub fn zero() -> Self {
+        Self::new(Vector::zeros(), Vector::zeros())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASS: Mass = Mass(1e6);
+
+    fn assert_close(a: Vector, b: Vector, tolerance: f32) {
+        assert!((a - b).norm() <= tolerance, "{:?} != {:?}", a, b);
+    }
+
+    /// The specific orbital energy, which is constant along an orbit
+    fn energy(eci: &Eci) -> f64 {
+        let r = to_f64(eci.position()).norm();
+        let v = to_f64(eci.velocity()).norm();
+        v * v / 2.0 - GRAVITY * f64::from(MASS.0) / r
+    }
+
+    fn ecis() -> Vec<Eci> {
+        vec![
+            // circular
+            Eci::new(Vector::new(2000.0, 0.0), Vector::new(0.0, 22.36068)),
+            // elliptic and clockwise
+            Eci::new(Vector::new(0.0, 1500.0), Vector::new(20.0, 5.0)),
+            // parabolic
+            Eci::new(Vector::new(-1000.0, 0.0), Vector::new(0.0, -44.72136)),
+            // hyperbolic
+            Eci::new(Vector::new(1000.0, 1000.0), Vector::new(-40.0, 30.0)),
+        ]
+    }
+
+    #[test]
+    fn test_from_mpv() {
+        for eci in ecis() {
+            let orbit = Orbit::from_mpv(MASS, eci.clone(), Time(100));
+            let at_epoch = orbit.eci(Time(100));
+            assert_close(at_epoch.position(), eci.position(), 0.05);
+            assert_close(at_epoch.velocity(), eci.velocity(), 1e-3);
+        }
+
+        let orbit = Orbit::from_mpv(MASS, ecis()[0].clone(), Time(0));
+        assert!(orbit.eccentricity() < 1e-4);
+        assert!(orbit.is_anticlockwise());
+        let orbit = Orbit::from_mpv(MASS, ecis()[1].clone(), Time(0));
+        assert!(!orbit.is_anticlockwise());
+    }
+
+    #[test]
+    fn test_propagation() {
+        for eci in ecis() {
+            let orbit = Orbit::from_mpv(MASS, eci.clone(), Time(0));
+            let initial = energy(&eci);
+            for &t in &[-500, 1, 77, 1000, 5000] {
+                let energy = energy(&orbit.eci(Time(t)));
+                assert!(
+                    (energy - initial).abs() <= initial.abs() * 1e-3 + 1e-2,
+                    "{} != {} at {}",
+                    energy,
+                    initial,
+                    t
+                );
+            }
+        }
+
+        // a circular orbit of radius 2000 takes 2pi * sqrt(2000^3 / 1e6) = 561.99 time units
+        let orbit = Orbit::from_mpv(MASS, ecis()[0].clone(), Time(0));
+        assert!((orbit.period().unwrap() - 561.99).abs() < 0.01);
+        assert_close(orbit.position(Time(562)), ecis()[0].position(), 1.0);
+        assert_close(orbit.position(Time(281)), -ecis()[0].position(), 1.0);
+    }
+
+    #[test]
+    fn test_time_reaching() {
+        let orbit = Orbit::from_mpv(MASS, ecis()[1].clone(), Time(0));
+        let period = orbit.period().unwrap();
+        let periapsis = orbit.peripapsis().norm();
+        let apoapsis = orbit.apoapsis().unwrap().norm();
+
+        let t = orbit.time_reaching(Length(apoapsis), Time(0)).unwrap();
+        assert!((orbit.position(t).norm() - apoapsis).abs() < 1.0);
+        let next = orbit
+            .time_reaching(Length(apoapsis), Time(t.0 + 1))
+            .unwrap();
+        assert!((f64::from(next.0 - t.0) - period).abs() <= 1.0);
+
+        let mid = (periapsis + apoapsis) / 2.0;
+        let t = orbit.time_reaching(Length(mid), Time(0)).unwrap();
+        assert!((orbit.position(t).norm() - mid).abs() < 10.0);
+        assert!(orbit
+            .time_reaching(Length(apoapsis + 10.0), Time(0))
+            .is_none());
+        assert!(orbit
+            .time_reaching(Length(periapsis - 10.0), Time(0))
+            .is_none());
+
+        let circular = Orbit::from_mpv(
+            MASS,
+            Eci::new(Vector::new(1e6, 0.0), Vector::new(0.0, 1.0)),
+            Time(0),
+        );
+        assert_eq!(circular.eccentricity(), 0.0);
+        assert_eq!(circular.time_reaching(Length(1e6), Time(5)), Some(Time(5)));
+        assert!(circular.time_reaching(Length(2e6), Time(5)).is_none());
+
+        let hyperbolic = Orbit::from_mpv(MASS, ecis()[3].clone(), Time(0));
+        let t = hyperbolic.time_reaching(Length(5000.0), Time(0)).unwrap();
+        assert!((hyperbolic.position(t).norm() - 5000.0).abs() < 50.0);
+    }
+
+    #[test]
+    fn test_path() {
+        for eci in ecis() {
+            let orbit = Orbit::from_mpv(MASS, eci, Time(0));
+            let path = orbit.path(Length(0.5), Length(10000.0));
+            assert!(path.len() > 4);
+            for point in &path {
+                assert!(point.norm() <= 10000.0 * 1.0001);
+            }
+            // the midpoint of each segment stays within a few times the error of the orbit
+            for pair in path.windows(2) {
+                let mid = to_f64((pair[0] + pair[1]) / 2.0);
+                let anomaly = orbit.direction * (mid.y.atan2(mid.x) - orbit.arg_periapsis);
+                let radius = orbit.position_at(anomaly).norm();
+                assert!(
+                    (mid.norm() - radius).abs() < 2.0,
+                    "{} != {}",
+                    mid.norm(),
+                    radius
+                );
+            }
+            if orbit.is_closed() {
+                assert_close(path[0], path[path.len() - 1], 1e-3);
+            }
+        }
+
+        let orbit = Orbit::from_mpv(MASS, ecis()[0].clone(), Time(0));
+        assert!(orbit.path(Length(0.5), Length(1000.0)).is_empty());
+    }
+
+    #[test]
+    fn test_radial() {
+        let at_rest = Eci::new(Vector::new(2000.0, 0.0), Vector::zeros());
+        let orbit = Orbit::from_mpv(MASS, at_rest, Time(0));
+        let mut last = 2000.0;
+        for t in (10..=100).step_by(10) {
+            let eci = orbit.eci(Time(t));
+            assert!(eci.position().x.is_finite() && eci.velocity().x.is_finite());
+            let distance = eci.position().norm();
+            assert!(distance < last, "The body must fall towards the parent");
+            assert!(eci.position().y.abs() < 20.0);
+            last = distance;
+        }
+
+        let outwards = Eci::new(Vector::new(0.0, 2000.0), Vector::new(0.0, 50.0));
+        let orbit = Orbit::from_mpv(MASS, outwards, Time(0));
+        let eci = orbit.eci(Time(100));
+        assert!(eci.position().y > 2000.0);
+        assert!(eci.position().x.abs() < 20.0);
+
+        let orbit = Orbit::from_mpv(MASS, Eci::zero(), Time(0));
+        assert!(orbit.eci(Time(10)).position().x.is_finite());
+    }
 }
@@ -0,0 +1,106 @@
+use std::f64::consts::PI;
+
+use getset::*;
+
+use super::*;
+
+/// The cost of an impulsive transfer between two circular orbits around the same body
+#[derive(Debug, Clone, Getters, CopyGetters, serde::Serialize, serde::Deserialize)]
+pub struct TransferCost {
+    /// The magnitude of the velocity change of each burn, in order
+    #[get = "pub"]
+    burns: Vec<f64>,
+    /// The total velocity change of all burns
+    #[get_copy = "pub"]
+    delta_v: f64,
+    #[get_copy = "pub"]
+    time_of_flight: f64,
+}
+
+impl TransferCost {
+    fn new(burns: Vec<f64>, time_of_flight: f64) -> Self {
+        Self {
+            delta_v: burns.iter().sum(),
+            burns,
+            time_of_flight,
+        }
+    }
+}
+
+/// Computes the Hohmann transfer from a circular orbit of radius `from` to one of radius `to`
+/// around a body of mass `mass`.
+pub fn hohmann(mass: Mass, from: Length, to: Length) -> TransferCost {
+    let mu = GRAVITY * f64::from(mass.0);
+    let (r1, r2) = (f64::from(from.0), f64::from(to.0));
+    let a = (r1 + r2) / 2.0;
+
+    let burn1 = (mu / r1).sqrt() * ((r2 / a).sqrt() - 1.0);
+    let burn2 = (mu / r2).sqrt() * (1.0 - (r1 / a).sqrt());
+    TransferCost::new(vec![burn1.abs(), burn2.abs()], PI * (a.powi(3) / mu).sqrt())
+}
+
+/// Computes the bi-elliptic transfer from a circular orbit of radius `from` to one of radius
+/// `to` around a body of mass `mass`, through an intermediate apoapsis at radius `apoapsis`.
+pub fn bi_elliptic(mass: Mass, from: Length, to: Length, apoapsis: Length) -> TransferCost {
+    let mu = GRAVITY * f64::from(mass.0);
+    let (r1, r2, rb) = (f64::from(from.0), f64::from(to.0), f64::from(apoapsis.0));
+    let a1 = (r1 + rb) / 2.0;
+    let a2 = (r2 + rb) / 2.0;
+    // vis-viva equation
+    let speed = |r: f64, a: f64| (2.0 * mu / r - mu / a).sqrt();
+
+    let burn1 = speed(r1, a1) - speed(r1, r1);
+    let burn2 = speed(rb, a2) - speed(rb, a1);
+    let burn3 = speed(r2, a2) - speed(r2, r2);
+    TransferCost::new(
+        vec![burn1.abs(), burn2.abs(), burn3.abs()],
+        PI * ((a1.powi(3) / mu).sqrt() + (a2.powi(3) / mu).sqrt()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The gravitational parameter of the Earth, in km^3/s^2
+    const EARTH: Mass = Mass(398_600.0);
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_hohmann() {
+        // from a low Earth orbit to a geostationary orbit
+        let cost = hohmann(EARTH, Length(6678.0), Length(42_164.0));
+        assert_close(cost.burns()[0], 2.426, 1e-3);
+        assert_close(cost.burns()[1], 1.467, 1e-3);
+        assert_close(cost.delta_v(), 3.893, 1e-3);
+        assert_close(cost.time_of_flight() / 3600.0, 5.275, 1e-3);
+
+        // the reverse transfer costs the same
+        let reverse = hohmann(EARTH, Length(42_164.0), Length(6678.0));
+        assert_close(reverse.delta_v(), cost.delta_v(), 1e-6);
+        assert_close(reverse.time_of_flight(), cost.time_of_flight(), 1e-6);
+    }
+
+    #[test]
+    fn test_bi_elliptic() {
+        let (from, to) = (Length(7000.0), Length(105_000.0));
+        let cost = bi_elliptic(EARTH, from, to, Length(210_000.0));
+        assert_eq!(cost.burns().len(), 3);
+        assert_close(cost.delta_v(), 4.0285, 1e-3);
+        assert_close(cost.time_of_flight() / 3600.0, 135.8, 0.1);
+
+        // cheaper but much slower than the Hohmann transfer
+        let direct = hohmann(EARTH, from, to);
+        assert_close(direct.delta_v(), 4.0463, 1e-3);
+        assert_close(direct.time_of_flight() / 3600.0, 18.32, 0.01);
+        assert!(cost.delta_v() < direct.delta_v());
+    }
+}
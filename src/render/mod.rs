@@ -0,0 +1,640 @@
+//! Rendering of a `System` through nested viewports, independent of the drawing backend.
+
+use crate::math::{Length, Orbit, Time, Vector};
+use crate::tree::{BodyId, BodyRef, Handler, LargeBody, LargeBodyId, SmallBody, System, Tree};
+
+mod svg;
+pub use svg::*;
+
+/// The maximum distance in pixels between a drawn orbit path and the actual orbit
+pub const ORBIT_PIXEL_ERROR: f32 = 0.5;
+/// The width of the viewport relative to the sphere of influence of a newly focused body
+const FOCUS_MARGIN: f32 = 2.5;
+
+#[derive(Debug)]
+pub struct Viewport {
+    pub at: Vector,
+    pub of: LargeBodyId,
+    /// The lengths of the sides of the viewport
+    pub dim: (Length, Length),
+}
+
+impl Viewport {
+    pub fn convert_pos(&self, pos: Vector) -> ViewportCoord {
+        let relative = pos - self.at;
+        (
+            0.5 + relative[0] / self.dim.0 .0,
+            0.5 + relative[1] / self.dim.1 .0,
+        )
+    }
+
+    /// Converts a point in the viewport to a position relative to the focused body.
+    ///
+    /// This is the inverse of `convert_pos`.
+    pub fn convert_coord(&self, coord: ViewportCoord) -> Vector {
+        self.at
+            + Vector::new(
+                (coord.0 - 0.5) * self.dim.0 .0,
+                (coord.1 - 0.5) * self.dim.1 .0,
+            )
+    }
+
+    /// Scales the viewport by `factor`, keeping the point at `around` in place.
+    ///
+    /// A factor greater than 1 zooms out.
+    pub fn zoom(&mut self, factor: f32, around: ViewportCoord) {
+        let anchor = self.convert_coord(around);
+        self.dim = (
+            Length(self.dim.0 .0 * factor),
+            Length(self.dim.1 .0 * factor),
+        );
+        self.at += anchor - self.convert_coord(around);
+    }
+
+    /// Moves the content of the viewport by `delta` viewport lengths.
+    pub fn pan(&mut self, delta: ViewportCoord) {
+        self.at -= Vector::new(delta.0 * self.dim.0 .0, delta.1 * self.dim.1 .0);
+    }
+
+    /// Centers the viewport on a large body and zooms to fit its sphere of influence.
+    ///
+    /// The viewport stays centered on the body as it moves,
+    /// because positions in the viewport are relative to the focused body.
+    /// Does nothing if the body is not in the tree.
+    pub fn focus(&mut self, tree: &Tree, id: LargeBodyId) {
+        let body = match tree.try_get_large_body(id) {
+            Some(body) => body,
+            None => return,
+        };
+        self.of = id;
+        self.at = Vector::new(0.0, 0.0);
+
+        let grav_radius = body.grav_radius().0;
+        if grav_radius.is_finite() {
+            let aspect = self.dim.1 .0 / self.dim.0 .0;
+            let width = grav_radius * 2.0 * FOCUS_MARGIN;
+            self.dim = (Length(width), Length(width * aspect));
+        }
+    }
+
+    /// Focuses on the parent of the focused body, if any.
+    pub fn focus_parent(&mut self, tree: &Tree) {
+        if let Some(parent) = tree.parent(self.of.into()) {
+            self.focus(tree, parent);
+        }
+    }
+
+    /// Focuses on the first large child of the focused body, if any.
+    pub fn focus_child(&mut self, tree: &Tree) {
+        let child = tree
+            .try_get_large_body(self.of)
+            .and_then(|body| large_children(body).into_iter().next());
+        if let Some(child) = child {
+            self.focus(tree, child);
+        }
+    }
+
+    /// Focuses on the large sibling `offset` places after the focused body, wrapping around.
+    pub fn focus_sibling(&mut self, tree: &Tree, offset: isize) {
+        let parent = match tree.parent(self.of.into()) {
+            Some(parent) => parent,
+            None => return,
+        };
+        let siblings = large_children(tree.get_large_body(parent));
+        let index = siblings
+            .iter()
+            .position(|&sibling| sibling == self.of)
+            .expect("Body is a child of its parent") as isize;
+        let len = siblings.len() as isize;
+        self.focus(tree, siblings[(index + offset).rem_euclid(len) as usize]);
+    }
+
+    /// Samples the path of an orbit in the viewport, clipped to `clip_radius` from the parent.
+    fn orbit_path(
+        &self,
+        orbit: &Orbit,
+        clip_radius: Length,
+        renderer: &impl Renderer,
+    ) -> Vec<ViewportCoord> {
+        let pixel = renderer.pixel_size();
+        let pixel_length = (pixel.0 * self.dim.0 .0).min(pixel.1 * self.dim.1 .0);
+        orbit
+            .path(Length(pixel_length * ORBIT_PIXEL_ERROR), clip_radius)
+            .into_iter()
+            .map(|point| self.convert_pos(point))
+            .collect()
+    }
+}
+
+pub type ViewportCoord = (f32, f32);
+
+/// The large children of a body in a stable order
+fn large_children(body: &LargeBody) -> Vec<LargeBodyId> {
+    body.children()
+        .into_iter()
+        .filter_map(|child| match child {
+            BodyRef::Large(child) => Some(child.id()),
+            BodyRef::Small(_) => None,
+        })
+        .collect()
+}
+
+pub trait Renderer: Sized {
+    /// The scale is the viewport length (in [0, 1]) for an object of Length(1.0) along each axis
+    fn render_body(&mut self, body: BodyRef<'_>, at: ViewportCoord, scale: (f32, f32));
+
+    /// Draws the orbit of a body as a polyline through `path`
+    fn render_orbit(&mut self, id: BodyId, path: &[ViewportCoord]);
+
+    /// The size of a pixel in viewport lengths along each axis
+    fn pixel_size(&self) -> (f32, f32);
+
+    /// Creates a renderer for a nested viewport centered at `center`,
+    /// where `scale` is the size of the nested viewport in this viewport along each axis.
+    ///
+    /// Returns `None` if the nested viewport is completely beyond this viewport.
+    fn proxy<'t>(
+        &'t mut self,
+        center: ViewportCoord,
+        scale: (f32, f32),
+    ) -> Option<ProxyRenderer<'t, Self>> {
+        let frame = Frame::IDENTITY.nest(center, scale)?;
+        Some(ProxyRenderer { inner: self, frame })
+    }
+}
+
+/// The placement of a nested viewport in the outermost viewport
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    center: ViewportCoord,
+    scale: (f32, f32),
+}
+
+impl Frame {
+    /// The frame covering the whole outermost viewport
+    const IDENTITY: Self = Self {
+        center: (0.5, 0.5),
+        scale: (1.0, 1.0),
+    };
+
+    /// The frame of a viewport centered at `center` in this frame,
+    /// where `scale` is the size of the nested viewport in this frame along each axis.
+    ///
+    /// Returns `None` if the nested viewport is completely beyond the outermost viewport.
+    fn nest(self, center: ViewportCoord, scale: (f32, f32)) -> Option<Self> {
+        let ret = Self {
+            center: self.translate(center),
+            scale: (self.scale.0 * scale.0, self.scale.1 * scale.1),
+        };
+        if ret.oob() {
+            None
+        } else {
+            Some(ret)
+        }
+    }
+
+    /// Converts a point in this frame to the outermost viewport.
+    fn translate(self, point: ViewportCoord) -> ViewportCoord {
+        (
+            self.center.0 + (point.0 - 0.5) * self.scale.0,
+            self.center.1 + (point.1 - 0.5) * self.scale.1,
+        )
+    }
+
+    /// Converts a point in the outermost viewport to this frame.
+    ///
+    /// This is the inverse of `translate`.
+    fn untranslate(self, point: ViewportCoord) -> ViewportCoord {
+        (
+            0.5 + (point.0 - self.center.0) / self.scale.0,
+            0.5 + (point.1 - self.center.1) / self.scale.1,
+        )
+    }
+
+    /// Converts the size of a pixel in the outermost viewport to this frame.
+    fn pixel_size(self, pixel: (f32, f32)) -> (f32, f32) {
+        (pixel.0 / self.scale.0, pixel.1 / self.scale.1)
+    }
+
+    /// whether the frame is completely beyond the outermost viewport
+    fn oob(self) -> bool {
+        let (a, b) = self.translate((0.0, 0.0));
+        let (c, d) = self.translate((1.0, 1.0));
+
+        a > 1.0 || b > 1.0 || c < 0.0 || d < 0.0
+    }
+}
+
+#[derive(Debug)]
+pub struct ProxyRenderer<'t, R: Renderer> {
+    inner: &'t mut R,
+    frame: Frame,
+}
+
+impl<'t, R: Renderer> ProxyRenderer<'t, R> {
+    /// A proxy covering the whole viewport of `inner`
+    fn identity(inner: &'t mut R) -> Self {
+        Self {
+            inner,
+            frame: Frame::IDENTITY,
+        }
+    }
+
+    /// Like `Renderer::proxy`, but renders to the same inner renderer,
+    /// so that nesting does not nest the types.
+    fn nest(&mut self, center: ViewportCoord, scale: (f32, f32)) -> Option<ProxyRenderer<'_, R>> {
+        let frame = self.frame.nest(center, scale)?;
+        Some(ProxyRenderer {
+            inner: &mut *self.inner,
+            frame,
+        })
+    }
+}
+
+impl<'u, R: Renderer> Renderer for ProxyRenderer<'u, R> {
+    fn render_body(&mut self, body: BodyRef<'_>, at: ViewportCoord, scale: (f32, f32)) {
+        let at = self.frame.translate(at);
+        let scale = (scale.0 * self.frame.scale.0, scale.1 * self.frame.scale.1);
+        self.inner.render_body(body, at, scale)
+    }
+
+    fn render_orbit(&mut self, id: BodyId, path: &[ViewportCoord]) {
+        let path = path
+            .iter()
+            .map(|&point| self.frame.translate(point))
+            .collect::<Vec<_>>();
+        self.inner.render_orbit(id, &path)
+    }
+
+    fn pixel_size(&self) -> (f32, f32) {
+        self.frame.pixel_size(self.inner.pixel_size())
+    }
+}
+
+/// Renders the focused body of the viewport and all its descendants.
+///
+/// The descendants of a child are rendered in a nested viewport covering its sphere of influence,
+/// which is skipped if it is beyond the viewport or smaller than a pixel.
+/// Nothing is rendered if the focused body has been removed.
+pub fn render<H: Handler>(
+    system: &System<H>,
+    t: Time,
+    viewport: &Viewport,
+    renderer: &mut impl Renderer,
+) {
+    let body = match system.tree().try_get_large_body(viewport.of) {
+        Some(body) => body,
+        None => return,
+    };
+    renderer.render_body(
+        BodyRef::Large(body),
+        viewport.convert_pos(Vector::new(0.0, 0.0)),
+        (viewport.dim.0.recip(), viewport.dim.1.recip()),
+    );
+
+    render_children(body, t, viewport, &mut ProxyRenderer::identity(renderer));
+}
+
+fn render_children<R: Renderer>(
+    body: &LargeBody,
+    t: Time,
+    viewport: &Viewport,
+    renderer: &mut ProxyRenderer<'_, R>,
+) {
+    for child in body.children() {
+        match child {
+            BodyRef::Large(child) => render_large(child, body.grav_radius(), t, viewport, renderer),
+            BodyRef::Small(child) => render_small(child, body, t, viewport, renderer),
+        }
+    }
+}
+
+fn render_large<R: Renderer>(
+    body: &LargeBody,
+    clip_radius: Length,
+    t: Time,
+    viewport: &Viewport,
+    renderer: &mut ProxyRenderer<'_, R>,
+) {
+    let orbit = body
+        .orbit()
+        .as_ref()
+        .expect("render_large only accepts large children");
+    let path = viewport.orbit_path(orbit, clip_radius, renderer);
+    renderer.render_orbit(body.id().into(), &path);
+    let at = viewport.convert_pos(orbit.position(t));
+    renderer.render_body(
+        BodyRef::Large(body),
+        at,
+        (viewport.dim.0.recip(), viewport.dim.1.recip()),
+    );
+
+    let (nested, scale) = match soi_viewport(body, viewport, renderer.pixel_size()) {
+        Some(nested) => nested,
+        None => return,
+    };
+    if let Some(mut renderer) = renderer.nest(at, scale) {
+        render_children(body, t, &nested, &mut renderer);
+    }
+}
+
+/// Returns the nested viewport covering the sphere of influence of a child of the focused body
+/// and its size in `viewport` along each axis.
+///
+/// Returns `None` if the sphere of influence is smaller than `pixel`,
+/// the size of a pixel in `viewport`.
+fn soi_viewport(
+    body: &LargeBody,
+    viewport: &Viewport,
+    pixel: (f32, f32),
+) -> Option<(Viewport, (f32, f32))> {
+    let soi = Length(body.grav_radius().0 * 2.0);
+    let scale = (soi.0 / viewport.dim.0 .0, soi.0 / viewport.dim.1 .0);
+    if scale.0 < pixel.0 && scale.1 < pixel.1 {
+        return None;
+    }
+    let nested = Viewport {
+        at: Vector::new(0.0, 0.0),
+        of: body.id(),
+        dim: (soi, soi),
+    };
+    Some((nested, scale))
+}
+
+fn render_small(
+    body: &SmallBody,
+    parent: &LargeBody,
+    t: Time,
+    viewport: &Viewport,
+    renderer: &mut impl Renderer,
+) {
+    if let Some(orbit) = body.orbit() {
+        let path = viewport.orbit_path(orbit, parent.grav_radius(), renderer);
+        renderer.render_orbit(body.id().into(), &path);
+    }
+    renderer.render_body(
+        BodyRef::Small(body),
+        viewport.convert_pos(body.eci_in(parent, t).position()),
+        (viewport.dim.0.recip(), viewport.dim.1.recip()),
+    )
+}
+
+/// Returns the body drawn closest to `coord` by `render` with `renderer` at `t`.
+///
+/// Bodies are hit within `tolerance` pixels of their drawn radius,
+/// so that bodies smaller than a pixel can still be picked.
+/// `coord` is converted back through the nested viewports drawn by `render`,
+/// so the bodies in nested viewports that are not drawn are not picked either.
+pub fn pick<H: Handler>(
+    system: &System<H>,
+    t: Time,
+    viewport: &Viewport,
+    renderer: &impl Renderer,
+    coord: ViewportCoord,
+    tolerance: f32,
+) -> Option<BodyId> {
+    let body = system.tree().try_get_large_body(viewport.of)?;
+    let mut picker = Picker {
+        coord,
+        pixel: renderer.pixel_size(),
+        tolerance,
+        hit: None,
+    };
+    let origin = Vector::new(0.0, 0.0);
+    picker.test(BodyRef::Large(body), origin, viewport, Frame::IDENTITY);
+    picker.test_children(body, t, viewport, Frame::IDENTITY);
+    picker.hit.map(|(id, _)| id)
+}
+
+/// Finds the body drawn closest to a point in the outermost viewport.
+#[derive(Debug)]
+struct Picker {
+    coord: ViewportCoord,
+    /// The size of a pixel in the outermost viewport
+    pixel: (f32, f32),
+    /// The extra distance in pixels around a body that still counts as a hit
+    tolerance: f32,
+    /// The closest hit and its distance from `coord` in pixels
+    hit: Option<(BodyId, f32)>,
+}
+
+impl Picker {
+    /// Tests a body at `position` relative to the focused body of `viewport`,
+    /// which is drawn in `frame`.
+    fn test(&mut self, body: BodyRef<'_>, position: Vector, viewport: &Viewport, frame: Frame) {
+        let pixel = frame.pixel_size(self.pixel);
+        let pixel = (pixel.0 * viewport.dim.0 .0, pixel.1 * viewport.dim.1 .0);
+        let target = viewport.convert_coord(frame.untranslate(self.coord));
+
+        let dx = (position.x - target.x) / pixel.0;
+        let dy = (position.y - target.y) / pixel.1;
+        let distance = dx.hypot(dy);
+        if distance > body.radius().0 / pixel.0 + self.tolerance {
+            return;
+        }
+        match self.hit {
+            Some((_, closest)) if closest <= distance => (),
+            _ => self.hit = Some((body.id(), distance)),
+        }
+    }
+
+    /// Tests the descendants of the focused body of `viewport` like `render_children`.
+    fn test_children(&mut self, body: &LargeBody, t: Time, viewport: &Viewport, frame: Frame) {
+        for child in body.children() {
+            match child {
+                BodyRef::Large(child) => {
+                    let orbit = child.orbit().as_ref().expect("Child bodies have an orbit");
+                    let position = orbit.position(t);
+                    self.test(BodyRef::Large(child), position, viewport, frame);
+
+                    let pixel = frame.pixel_size(self.pixel);
+                    if let Some((nested, scale)) = soi_viewport(child, viewport, pixel) {
+                        let at = viewport.convert_pos(position);
+                        if let Some(frame) = frame.nest(at, scale) {
+                            self.test_children(child, t, &nested, frame);
+                        }
+                    }
+                }
+                BodyRef::Small(child) => {
+                    let position = child.eci_in(body, t).position();
+                    self.test(BodyRef::Small(child), position, viewport, frame);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Compares `actual` with the golden file, or overwrites it if `UPDATE_GOLDEN` is set.
+    fn assert_golden(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, actual).expect("Failed to write golden file");
+            return;
+        }
+        let expected = fs::read_to_string(&path).expect("Failed to read golden file");
+        assert!(
+            expected == actual,
+            "{} does not match, rerun with UPDATE_GOLDEN=1 to update it",
+            path.display()
+        );
+    }
+
+    fn system() -> System<()> {
+        let schema = json!({
+            "surface_radius": 100.0,
+            "grav_radius": 1e30,
+            "mass": 1e6,
+            "children": [
+                {
+                    "surface_radius": 20.0,
+                    "grav_radius": 400.0,
+                    "mass": 1000.0,
+                    "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+                    "children": [
+                        {
+                            "surface_radius": 5.0,
+                            "grav_radius": 30.0,
+                            "mass": 10.0,
+                            "eci": {"position": [150.0, 0.0], "velocity": [0.0, 2.6]},
+                        }
+                    ],
+                },
+                {
+                    "surface_radius": 10.0,
+                    "grav_radius": 200.0,
+                    "mass": 500.0,
+                    "eci": {"position": [-1200.0, 0.0], "velocity": [0.0, -25.0]},
+                },
+            ],
+        });
+        System::from_schema(serde_json::from_value(schema).unwrap(), ())
+    }
+
+    fn viewport(system: &System<()>) -> Viewport {
+        Viewport {
+            at: Vector::new(0.0, 0.0),
+            of: system.tree().root().id(),
+            dim: (Length(5000.0), Length(3750.0)),
+        }
+    }
+
+    #[test]
+    fn test_viewport() {
+        let system = system();
+        let mut viewport = viewport(&system);
+        let pos = Vector::new(1234.0, -567.0);
+        let coord = viewport.convert_pos(pos);
+        assert!((viewport.convert_coord(coord) - pos).norm() < 1e-2);
+
+        let around = (0.2, 0.7);
+        let anchor = viewport.convert_coord(around);
+        viewport.zoom(0.5, around);
+        assert!((viewport.convert_coord(around) - anchor).norm() < 1e-2);
+    }
+
+    #[test]
+    fn test_removed_focus() {
+        let mut system = system();
+        let planet = system.tree().root().children()[0].borrow_large().id();
+        let mut viewport = viewport(&system);
+        viewport.focus(system.tree(), planet);
+        system.remove_body(planet.into());
+
+        let mut renderer = SvgRenderer::new(400.0, 300.0, true);
+        render(&system, Time(100), &viewport, &mut renderer);
+        assert_eq!(
+            renderer.finish(),
+            SvgRenderer::new(400.0, 300.0, true).finish()
+        );
+
+        viewport.focus_child(system.tree());
+        viewport.focus(system.tree(), planet);
+        assert_eq!(viewport.of, planet);
+        viewport.focus_parent(system.tree());
+        assert_eq!(viewport.of, planet);
+    }
+
+    /// Records where bodies are drawn
+    struct Recorder {
+        pixel: (f32, f32),
+        bodies: Vec<(BodyId, ViewportCoord)>,
+    }
+
+    impl Renderer for Recorder {
+        fn render_body(&mut self, body: BodyRef<'_>, at: ViewportCoord, _: (f32, f32)) {
+            self.bodies.push((body.id(), at));
+        }
+
+        fn render_orbit(&mut self, _: BodyId, _: &[ViewportCoord]) {}
+
+        fn pixel_size(&self) -> (f32, f32) {
+            self.pixel
+        }
+    }
+
+    fn record(system: &System<()>, viewport: &Viewport, svg: &SvgRenderer) -> Recorder {
+        let mut recorder = Recorder {
+            pixel: svg.pixel_size(),
+            bodies: Vec::new(),
+        };
+        render(system, Time(100), viewport, &mut recorder);
+        recorder
+    }
+
+    #[test]
+    fn test_pick() {
+        // the scene of the golden file
+        let system = system();
+        let viewport = viewport(&system);
+        let svg = SvgRenderer::new(400.0, 300.0, true);
+        let pick_at =
+            |coord, tolerance| pick(&system, Time(100), &viewport, &svg, coord, tolerance);
+
+        let bodies = record(&system, &viewport, &svg).bodies;
+        assert_eq!(bodies.len(), 4);
+        for &(id, at) in &bodies {
+            assert_eq!(pick_at(at, 0.0), Some(id));
+        }
+        assert_eq!(pick_at((0.1, 0.9), 2.0), None);
+
+        // the moon, drawn in the nested viewport of the planet, is smaller than a pixel
+        let (moon, at) = bodies[2];
+        let planet = bodies[1].1;
+        let away = (at.0 - planet.0, at.1 - planet.1);
+        let norm = away.0.hypot(away.1);
+        let pixel = svg.pixel_size();
+        let near = (
+            at.0 + away.0 / norm * 3.0 * pixel.0,
+            at.1 + away.1 / norm * 3.0 * pixel.1,
+        );
+        assert_eq!(pick_at(near, 3.0), Some(moon));
+        assert_eq!(pick_at(near, 1.0), None);
+
+        // the moon is not drawn when the sphere of influence of the planet is below a pixel
+        let tiny = SvgRenderer::new(4.0, 3.0, false);
+        let drawn = record(&system, &viewport, &tiny).bodies;
+        assert!(drawn.iter().all(|&(id, _)| id != moon));
+        let hit = pick(&system, Time(100), &viewport, &tiny, at, 0.0);
+        assert_ne!(hit, Some(moon));
+    }
+
+    #[test]
+    fn test_render_golden() {
+        let system = system();
+        let mut renderer = SvgRenderer::new(400.0, 300.0, true);
+        render(&system, Time(100), &viewport(&system), &mut renderer);
+        assert_golden("render.svg", &renderer.finish());
+    }
+}
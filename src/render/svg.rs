@@ -0,0 +1,120 @@
+use std::fmt::Write;
+
+use super::*;
+
+const BACKGROUND_COLOR: &str = "black";
+const LARGE_BODY_COLOR: &str = "#d0c8b0";
+const SMALL_BODY_COLOR: &str = "#80c0ff";
+const SOI_COLOR: &str = "#ffffff26";
+const ORBIT_COLOR: &str = "#ffffff66";
+const LABEL_COLOR: &str = "white";
+/// The minimum radius of a body in pixels, so that tiny bodies remain visible
+const MIN_BODY_RADIUS: f32 = 1.5;
+
+/// Renders to an SVG document, mapping the viewport onto an image of the given size.
+///
+/// The y-axis of the viewport points up in the image, like in the canvas renderer.
+#[derive(Debug)]
+pub struct SvgRenderer {
+    width: f32,
+    height: f32,
+    /// Whether each body is labelled with the index of its ID
+    labels: bool,
+    elements: String,
+}
+
+impl SvgRenderer {
+    pub fn new(width: f32, height: f32, labels: bool) -> Self {
+        Self {
+            width,
+            height,
+            labels,
+            elements: String::new(),
+        }
+    }
+
+    /// Returns the SVG document of the rendered elements.
+    pub fn finish(self) -> String {
+        format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+                "\n",
+                r#"<rect width="{w}" height="{h}" fill="{bg}"/>"#,
+                "\n{elements}</svg>\n",
+            ),
+            w = self.width,
+            h = self.height,
+            bg = BACKGROUND_COLOR,
+            elements = self.elements,
+        )
+    }
+
+    fn to_pixels(&self, at: ViewportCoord) -> (f32, f32) {
+        (at.0 * self.width, (1.0 - at.1) * self.height)
+    }
+}
+
+impl Renderer for SvgRenderer {
+    fn render_body(&mut self, body: BodyRef<'_>, at: ViewportCoord, scale: (f32, f32)) {
+        let (x, y) = self.to_pixels(at);
+        let pixels_per_length = scale.0 * self.width;
+
+        if let BodyRef::Large(body) = body {
+            let soi = body.grav_radius().0 * pixels_per_length;
+            if soi.is_finite() {
+                writeln!(
+                    self.elements,
+                    r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="none" stroke="{}"/>"#,
+                    x, y, soi, SOI_COLOR,
+                )
+                .expect("Writing to a string never fails");
+            }
+        }
+
+        let color = match body {
+            BodyRef::Large(_) => LARGE_BODY_COLOR,
+            BodyRef::Small(_) => SMALL_BODY_COLOR,
+        };
+        let radius = (body.radius().0 * pixels_per_length).max(MIN_BODY_RADIUS);
+        writeln!(
+            self.elements,
+            r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}"/>"#,
+            x, y, radius, color,
+        )
+        .expect("Writing to a string never fails");
+
+        if self.labels {
+            writeln!(
+                self.elements,
+                r#"<text x="{:.2}" y="{:.2}" fill="{}" font-size="10">{}</text>"#,
+                x + radius,
+                y - radius,
+                LABEL_COLOR,
+                body.id().index(),
+            )
+            .expect("Writing to a string never fails");
+        }
+    }
+
+    fn render_orbit(&mut self, _: BodyId, path: &[ViewportCoord]) {
+        if path.is_empty() {
+            return;
+        }
+        let mut points = String::new();
+        for &point in path {
+            let (x, y) = self.to_pixels(point);
+            write!(points, "{:.2},{:.2} ", x, y).expect("Writing to a string never fails");
+        }
+        writeln!(
+            self.elements,
+            r#"<polyline points="{}" fill="none" stroke="{}"/>"#,
+            points.trim_end(),
+            ORBIT_COLOR,
+        )
+        .expect("Writing to a string never fails");
+    }
+
+    fn pixel_size(&self) -> (f32, f32) {
+        (self.width.recip(), self.height.recip())
+    }
+}
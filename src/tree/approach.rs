@@ -0,0 +1,260 @@
+use getset::*;
+
+use super::*;
+use crate::math::{Length, Time, Vector};
+
+/// The number of samples per period of the fastest orbit involved when searching for the
+/// closest approach
+const SAMPLES_PER_PERIOD: f64 = 32.0;
+/// The maximum number of samples when searching for the closest approach
+const MAX_SAMPLES: i32 = 4096;
+
+/// The point of minimum separation between two bodies
+#[derive(Debug, Clone, CopyGetters, serde::Serialize, serde::Deserialize)]
+pub struct Approach {
+    #[get_copy = "pub"]
+    time: Time,
+    #[get_copy = "pub"]
+    distance: Length,
+    /// The velocity of the body relative to the target at `time`
+    #[get_copy = "pub"]
+    relative_velocity: Vector,
+}
+
+impl Tree {
+    /// Finds the time in `from..=to` at which `body` is closest to `target`.
+    ///
+    /// The bodies may be anywhere in the tree.
+    /// Their motion is predicted from the orbits up to their closest common ancestor,
+    /// assuming that no body leaves its current sphere of influence within the window.
+    ///
+    /// Returns `None` if `to` is earlier than `from` or either body is not in the tree.
+    pub fn closest_approach(
+        &self,
+        body: BodyId,
+        target: BodyId,
+        from: Time,
+        to: Time,
+    ) -> Option<Approach> {
+        if to < from {
+            return None;
+        }
+
+        let (body_path, target_path) = self.paths_to_common_ancestor(body, target)?;
+        let min_period = body_path
+            .iter()
+            .chain(&target_path)
+            .filter_map(|&id| self.get_body(id).orbit()?.period())
+            .fold(f64::INFINITY, f64::min);
+
+        let step = sample_step(min_period, to.0 - from.0);
+
+        let relative_eci = |t: i32| {
+            self.relative_eci(body, target, Time(t))
+                .expect("Both bodies exist")
+        };
+        let distance = |t: i32| relative_eci(t).position().norm();
+
+        let mut samples = Vec::new();
+        let mut t = from.0;
+        loop {
+            samples.push((t, distance(t)));
+            if t == to.0 {
+                break;
+            }
+            t = t.saturating_add(step).min(to.0);
+        }
+
+        // refine every local minimum, since the global minimum may lie between other samples
+        let mut best = samples[0];
+        for i in 0..samples.len() {
+            let lo = samples[i.saturating_sub(1)];
+            let hi = samples[(i + 1).min(samples.len() - 1)];
+            if samples[i].1 > lo.1 || samples[i].1 > hi.1 {
+                continue;
+            }
+
+            let refined = refine_minimum(lo.0, hi.0, distance);
+            if refined.1 < best.1 {
+                best = refined;
+            }
+        }
+
+        let (time, _) = best;
+        let eci = relative_eci(time);
+        Some(Approach {
+            time: Time(time),
+            distance: Length(eci.position().norm()),
+            relative_velocity: eci.velocity(),
+        })
+    }
+}
+
+/// The interval between samples of the motion of bodies over `window`,
+/// where `min_period` is the period of the fastest orbit involved, or infinite if none is closed
+pub(super) fn sample_step(min_period: f64, window: i32) -> i32 {
+    let step = if min_period.is_finite() {
+        (min_period / SAMPLES_PER_PERIOD) as i32
+    } else {
+        window / 64
+    };
+    step.max(window / MAX_SAMPLES).max(1)
+}
+
+/// Ternary search for the minimum of a unimodal function over the integers in `lo..=hi`
+pub(super) fn refine_minimum(mut lo: i32, mut hi: i32, f: impl Fn(i32) -> f32) -> (i32, f32) {
+    while hi - lo > 2 {
+        let m1 = lo + (hi - lo) / 3;
+        let m2 = hi - (hi - lo) / 3;
+        if f(m1) < f(m2) {
+            hi = m2;
+        } else {
+            lo = m1;
+        }
+    }
+
+    (lo..=hi)
+        .map(|t| (t, f(t)))
+        .fold((lo, f32::INFINITY), |best, sample| {
+            if sample.1 < best.1 {
+                sample
+            } else {
+                best
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Eci, Mass, GRAVITY};
+
+    const MASS: f64 = 1e6;
+
+    fn system() -> System<()> {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "mass": MASS,
+        }))
+        .expect("Invalid schema");
+        System::from_schema(schema, ())
+    }
+
+    /// Spawns a small body on a counterclockwise circular orbit around the root.
+    fn spawn_circular(system: &mut System<()>, radius: f32, angle: f32) -> BodyId {
+        let root = system.tree().root().id();
+        let speed = (GRAVITY * MASS / f64::from(radius)).sqrt() as f32;
+        let (sin, cos) = angle.sin_cos();
+        let eci = Eci::new(
+            Vector::new(cos, sin) * radius,
+            Vector::new(-sin, cos) * speed,
+        );
+        system
+            .spawn_small(root, Mass(1.0), Length(1.0), eci, Time(0))
+            .into()
+    }
+
+    #[test]
+    fn test_circular_encounter() {
+        let mut system = system();
+        let inner = spawn_circular(&mut system, 2000.0, 0.0);
+        let outer = spawn_circular(&mut system, 3000.0, 0.5);
+
+        // the inner body catches up with the outer body at the conjunction
+        let angular_velocity = |radius: f64| (GRAVITY * MASS / radius.powi(3)).sqrt();
+        let conjunction = 0.5 / (angular_velocity(2000.0) - angular_velocity(3000.0));
+
+        let tree = system.tree();
+        let approach = tree
+            .closest_approach(inner, outer, Time(0), Time(1000))
+            .expect("Window is not empty");
+        assert!((f64::from(approach.time().0) - conjunction).abs() <= 1.0);
+        assert!((approach.distance().0 - 1000.0).abs() < 1.0);
+
+        let brute_force = (0..=1000)
+            .map(|t| {
+                let eci = tree.relative_eci(inner, outer, Time(t)).unwrap();
+                eci.position().norm()
+            })
+            .fold(f32::INFINITY, f32::min);
+        assert!((approach.distance().0 - brute_force).abs() < 1e-2);
+
+        assert!(tree
+            .closest_approach(inner, outer, Time(10), Time(0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_different_branches() {
+        // a planet with a moon, and another planet ahead of it on an outer orbit
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "mass": MASS,
+            "children": [{
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+                "children": [{
+                    "surface_radius": 2.0,
+                    "grav_radius": 20.0,
+                    "mass": 10.0,
+                    "eci": {"position": [100.0, 0.0], "velocity": [0.0, 3.16]},
+                }],
+            }, {
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2632.7, 1438.3], "velocity": [-8.753, 16.022]},
+            }],
+        }))
+        .expect("Invalid schema");
+        let system = System::from_schema(schema, ());
+        let tree = system.tree();
+        let planets = tree.root().children();
+        let moon = planets[0].borrow_large().children()[0].id();
+        let target = planets[1].id();
+
+        let approach = tree
+            .closest_approach(moon, target, Time(0), Time(1000))
+            .expect("Window is not empty");
+        let (time, distance) = (0..=1000)
+            .map(|t| {
+                let eci = tree.relative_eci(moon, target, Time(t)).unwrap();
+                (t, eci.position().norm())
+            })
+            .fold((0, f32::INFINITY), |best, sample| {
+                if sample.1 < best.1 {
+                    sample
+                } else {
+                    best
+                }
+            });
+        assert_eq!(approach.time(), Time(time));
+        assert!((approach.distance().0 - distance).abs() < 1e-2);
+        // the moon is within its orbit radius of the planet at the conjunction
+        assert!((approach.distance().0 - 1000.0).abs() < 100.0);
+
+        let reverse = tree
+            .closest_approach(target, moon, Time(0), Time(1000))
+            .expect("Window is not empty");
+        assert_eq!(reverse.time(), approach.time());
+        let velocity = reverse.relative_velocity() + approach.relative_velocity();
+        assert!(velocity.norm() < 1e-3);
+    }
+
+    #[test]
+    fn test_removed() {
+        let mut system = system();
+        let body = spawn_circular(&mut system, 2000.0, 0.0);
+        let target = spawn_circular(&mut system, 3000.0, 0.0);
+        system.remove_body(target);
+        let tree = system.tree();
+        assert!(tree
+            .closest_approach(body, target, Time(0), Time(100))
+            .is_none());
+        assert!(tree
+            .closest_approach(target, body, Time(0), Time(100))
+            .is_none());
+    }
+}
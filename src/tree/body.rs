@@ -2,14 +2,46 @@ use std::collections::HashMap;
 
 use getset::*;
 
-use crate::math::{Eci, Length, Mass, Orbit, Time};
+use crate::math::{Eci, Length, Mass, Orbit, Time, Vector};
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-pub struct BodyId(pub u32);
+/// Identifies a body in a `Tree`.
+///
+/// The index of a removed body may be reused by a later body, but with a higher generation,
+/// so a `BodyId` held after its body is removed never refers to another body.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    CopyGetters,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct BodyId {
+    #[get_copy = "pub"]
+    index: u32,
+    #[get_copy = "pub"]
+    generation: u32,
+}
 
 macro_rules! sl_body {
     ($name:ident) => {
-        #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+        #[derive(
+            Debug,
+            Clone,
+            Copy,
+            Hash,
+            PartialEq,
+            Eq,
+            PartialOrd,
+            Ord,
+            serde::Serialize,
+            serde::Deserialize,
+        )]
         pub struct $name(pub(super) BodyId);
 
         impl AsRef<BodyId> for $name {
@@ -28,6 +60,42 @@ macro_rules! sl_body {
 sl_body!(LargeBodyId);
 sl_body!(SmallBodyId);
 
+/// Allocates `BodyId`s, reusing the indices of removed bodies with a bumped generation.
+#[derive(Debug, Clone, Default)]
+pub(super) struct IdAllocator {
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+impl IdAllocator {
+    pub(super) fn alloc(&mut self) -> BodyId {
+        match self.free.pop() {
+            Some(index) => BodyId {
+                index,
+                generation: self.generations[index as usize],
+            },
+            None => {
+                let index = self.generations.len() as u32;
+                self.generations.push(0);
+                BodyId {
+                    index,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Marks the ID as no longer used.
+    ///
+    /// Subsequent allocations of the same index have a higher generation than `id`.
+    pub(super) fn free(&mut self, id: BodyId) {
+        let generation = &mut self.generations[id.index as usize];
+        debug_assert_eq!(*generation, id.generation, "ID freed twice");
+        *generation += 1;
+        self.free.push(id.index);
+    }
+}
+
 #[derive(Debug)]
 pub enum Body {
     Large(LargeBody),
@@ -57,6 +125,36 @@ pub enum BodyRef<'t> {
 }
 
 impl<'t> BodyRef<'t> {
+    pub fn id(self) -> BodyId {
+        match self {
+            Self::Large(body) => body.id().into(),
+            Self::Small(body) => body.id().into(),
+        }
+    }
+
+    pub fn mass(self) -> Mass {
+        match self {
+            Self::Large(body) => body.mass(),
+            Self::Small(body) => body.mass(),
+        }
+    }
+
+    /// Returns the radius of the body, which is the `surface_radius` for large bodies.
+    pub fn radius(self) -> Length {
+        match self {
+            Self::Large(body) => body.surface_radius(),
+            Self::Small(body) => body.radius(),
+        }
+    }
+
+    /// Returns the orbit of the body around its parent, or `None` for the root body.
+    pub fn orbit(self) -> Option<&'t Orbit> {
+        match self {
+            Self::Large(body) => body.orbit().as_ref(),
+            Self::Small(body) => body.orbit(),
+        }
+    }
+
     pub fn borrow_large(self) -> &'t LargeBody {
         match self {
             Self::Large(body) => body,
@@ -79,6 +177,15 @@ pub enum BodyMut<'t> {
 }
 
 impl<'t> BodyMut<'t> {
+    pub(super) fn set_orbit(self, orbit: Orbit) {
+        match self {
+            Self::Large(body) => {
+                body.set_orbit(Some(orbit));
+            }
+            Self::Small(body) => body.set_orbit(orbit),
+        }
+    }
+
     pub fn borrow_large(self) -> &'t mut LargeBody {
         match self {
             Self::Large(body) => body,
@@ -94,7 +201,7 @@ impl<'t> BodyMut<'t> {
     }
 }
 
-#[derive(Debug, Getters, CopyGetters, Setters)]
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
 pub struct LargeBody {
     #[get_copy = "pub"]
     pub(super) id: LargeBodyId,
@@ -111,9 +218,41 @@ pub struct LargeBody {
     #[get = "pub"]
     #[set = "pub(super)"]
     pub(super) orbit: Option<Orbit>,
+    #[get_copy = "pub"]
+    pub(super) rotation: Option<Rotation>,
 }
 
 impl LargeBody {
+    /// Returns the angle of the body-fixed x-axis at time `t`,
+    /// anticlockwise from the x-axis of the frame of this body.
+    pub fn rotation_angle(&self, t: Time) -> f32 {
+        self.rotation.map_or(0.0, |rotation| rotation.angle(t))
+    }
+
+    /// Converts a longitude on the surface to a position in the frame of this body at time `t`.
+    ///
+    /// Longitudes are body-fixed angles, anticlockwise from the body-fixed x-axis.
+    pub fn surface_position(&self, longitude: f32, t: Time) -> Vector {
+        let angle = longitude + self.rotation_angle(t);
+        Vector::new(angle.cos(), angle.sin()) * self.surface_radius.0
+    }
+
+    /// Converts a position in the frame of this body at time `t` to the longitude of the
+    /// surface point below it.
+    pub fn longitude(&self, position: Vector, t: Time) -> f32 {
+        let longitude = position.y.atan2(position.x) - self.rotation_angle(t);
+        longitude.rem_euclid(2.0 * std::f32::consts::PI)
+    }
+
+    /// Returns the velocity of a point on the surface in the frame of this body.
+    pub fn surface_velocity(&self, longitude: f32, t: Time) -> Vector {
+        let angular_velocity = self
+            .rotation
+            .map_or(0.0, |rotation| rotation.angular_velocity());
+        let position = self.surface_position(longitude, t);
+        Vector::new(-position.y, position.x) * angular_velocity
+    }
+
     pub fn get_child<'t>(&'t self, id: BodyId) -> Option<BodyRef<'t>> {
         if let Some(body) = self.large.get(&LargeBodyId(id)) {
             Some(BodyRef::Large(body))
@@ -161,23 +300,147 @@ impl LargeBody {
     }
 }
 
-#[derive(Debug, CopyGetters, Getters, Setters)]
+#[derive(Debug, Clone, CopyGetters, Getters)]
 pub struct SmallBody {
     #[get_copy = "pub"]
-    id: SmallBodyId,
+    pub(super) id: SmallBodyId,
     #[get_copy = "pub"]
-    mass: Mass,
+    pub(super) mass: Mass,
     #[get_copy = "pub"]
-    radius: Length,
+    pub(super) radius: Length,
     #[get = "pub"]
-    #[set = "pub(super)"]
-    orbit: Orbit,
+    pub(super) motion: Motion,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Getters, CopyGetters)]
+impl SmallBody {
+    /// Returns the orbit of the body, or `None` if it has landed.
+    pub fn orbit(&self) -> Option<&Orbit> {
+        match &self.motion {
+            Motion::Orbiting(orbit) => Some(orbit),
+            Motion::Landed(_) => None,
+        }
+    }
+
+    pub(super) fn set_orbit(&mut self, orbit: Orbit) {
+        self.motion = Motion::Orbiting(orbit);
+    }
+
+    /// Returns the ECI of the body relative to its parent.
+    ///
+    /// `parent` must be the parent of this body.
+    pub fn eci_in(&self, parent: &LargeBody, t: Time) -> Eci {
+        match &self.motion {
+            Motion::Orbiting(orbit) => orbit.eci(t),
+            Motion::Landed(landing) => Eci::new(
+                parent.surface_position(landing.longitude, t),
+                parent.surface_velocity(landing.longitude, t),
+            ),
+        }
+    }
+}
+
+/// How a small body moves relative to its parent
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Motion {
+    Orbiting(Orbit),
+    /// Resting on the surface of the parent body
+    Landed(Landing),
+}
+
+#[derive(Debug, Clone, Copy, CopyGetters, serde::Serialize, serde::Deserialize)]
+pub struct Landing {
+    /// The longitude of the body on the surface of its parent
+    #[get_copy = "pub"]
+    pub(super) longitude: f32,
+}
+
+/// An error when changing the motion of a body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionError {
+    /// Only landed bodies can be launched
+    NotLanded,
+    /// The root body is the frame of the tree and cannot move
+    Root,
+    /// The body is not in the tree
+    Removed,
+}
+
+impl std::fmt::Display for MotionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NotLanded => write!(f, "Only landed bodies can be launched"),
+            Self::Root => write!(f, "The root body cannot move"),
+            Self::Removed => write!(f, "The body is not in the tree"),
+        }
+    }
+}
+
+impl std::error::Error for MotionError {}
+
+/// The rotation of a large body about its center
+#[derive(Debug, Clone, Copy, CopyGetters, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "RotationSchema")]
+pub struct Rotation {
+    /// The time for a full rotation, which is negative for clockwise rotation
+    #[get_copy = "pub"]
+    period: f32,
+    /// The angle of the body-fixed x-axis at time 0
+    #[get_copy = "pub"]
+    initial_angle: f32,
+}
+
+impl Rotation {
+    /// Returns `Err` if the period is zero or either value is not finite.
+    pub fn new(period: f32, initial_angle: f32) -> Result<Self, String> {
+        if period == 0.0 || !period.is_finite() {
+            return Err(format!("period must be nonzero and finite, got {}", period));
+        }
+        if !initial_angle.is_finite() {
+            return Err(format!(
+                "initial_angle must be finite, got {}",
+                initial_angle
+            ));
+        }
+        Ok(Self {
+            period,
+            initial_angle,
+        })
+    }
+
+    /// The rotation speed in radians per unit time, which is negative for clockwise rotation
+    pub fn angular_velocity(self) -> f32 {
+        2.0 * std::f32::consts::PI / self.period
+    }
+
+    /// Returns the angle of the body-fixed x-axis at time `t`.
+    pub fn angle(self, t: Time) -> f32 {
+        let turns = (f64::from(t.0) / f64::from(self.period)).fract();
+        (f64::from(self.initial_angle) + turns * 2.0 * std::f64::consts::PI) as f32
+    }
+}
+
+/// The unvalidated fields of a `Rotation`
+#[derive(serde::Deserialize)]
+struct RotationSchema {
+    period: f32,
+    #[serde(default)]
+    initial_angle: f32,
+}
+
+impl std::convert::TryFrom<RotationSchema> for Rotation {
+    type Error = String;
+
+    fn try_from(schema: RotationSchema) -> Result<Self, Self::Error> {
+        Self::new(schema.period, schema.initial_angle)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Getters, CopyGetters)]
 pub struct LargeBodySchema {
     #[get_copy = "pub"]
     surface_radius: Length,
+    /// The radius of the sphere of influence, which is always infinite for the root body
+    #[serde(default = "infinite_length")]
     #[get_copy = "pub"]
     grav_radius: Length,
     #[get_copy = "pub"]
@@ -185,6 +448,34 @@ pub struct LargeBodySchema {
     #[get = "pub"]
     eci: Option<Eci>,
     #[serde(default)]
+    #[get_copy = "pub"]
+    rotation: Option<Rotation>,
+    #[serde(default)]
     #[get = "pub"]
     children: Vec<LargeBodySchema>,
 }
+
+fn infinite_length() -> Length {
+    Length(f32::INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation() {
+        let rotation = Rotation::new(-100.0, 1.0).expect("Valid rotation");
+        assert_eq!(rotation.angle(Time(0)), 1.0);
+        assert!(rotation.angular_velocity() < 0.0);
+
+        assert!(Rotation::new(0.0, 0.0).is_err());
+        assert!(Rotation::new(f32::INFINITY, 0.0).is_err());
+        assert!(Rotation::new(f32::NAN, 0.0).is_err());
+        assert!(Rotation::new(100.0, f32::NAN).is_err());
+
+        let parsed: Rotation = serde_json::from_str(r#"{"period": 100.0}"#).unwrap();
+        assert_eq!(parsed.period(), 100.0);
+        assert!(serde_json::from_str::<Rotation>(r#"{"period": 0.0}"#).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use super::*;
+
+/// A disagreement between two systems found by `System::diff`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    Body { id: BodyId, kind: DiffKind },
+    Event { id: EventId, kind: DiffKind },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    /// Only exists in the system `diff` is called on
+    OnlyLeft,
+    /// Only exists in the system passed to `diff`
+    OnlyRight,
+    /// Exists in both systems with a different state
+    Different,
+}
+
+/// The 64-bit FNV-1a hash, which is the same for every platform and build.
+///
+/// Integers are written as little-endian bytes, with `usize` and `isize` widened to 64 bits,
+/// so `Hash` implementations write the same bytes on 32-bit and 64-bit targets.
+#[derive(Debug, Clone)]
+pub(super) struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+fn hash_with(f: impl FnOnce(&mut Fnv)) -> u64 {
+    let mut hasher = Fnv::default();
+    f(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_body(tree: &Tree, body: BodyRef<'_>) -> u64 {
+    hash_with(|state| {
+        body.id().hash(state);
+        match tree.parent(body.id()) {
+            Some(parent) => {
+                state.write_u8(1);
+                parent.hash(state);
+            }
+            None => state.write_u8(0),
+        }
+        state.write_u32(body.mass().0.to_bits());
+        state.write_u32(body.radius().0.to_bits());
+        match body {
+            BodyRef::Large(body) => {
+                state.write_u8(0);
+                state.write_u32(body.grav_radius().0.to_bits());
+                match body.orbit() {
+                    Some(orbit) => {
+                        state.write_u8(1);
+                        orbit.hash(state);
+                    }
+                    None => state.write_u8(0),
+                }
+                match body.rotation() {
+                    Some(rotation) => {
+                        state.write_u8(1);
+                        state.write_u32(rotation.period().to_bits());
+                        state.write_u32(rotation.initial_angle().to_bits());
+                    }
+                    None => state.write_u8(0),
+                }
+            }
+            BodyRef::Small(body) => {
+                state.write_u8(1);
+                match body.motion() {
+                    Motion::Orbiting(orbit) => {
+                        state.write_u8(0);
+                        orbit.hash(state);
+                    }
+                    Motion::Landed(landing) => {
+                        state.write_u8(1);
+                        state.write_u32(landing.longitude().to_bits());
+                    }
+                }
+            }
+        }
+    })
+}
+
+impl<H: Handler> System<H> {
+    /// Computes a hash of the bodies and the pending events.
+    ///
+    /// The hash does not depend on the order of insertion into the tree or the queue,
+    /// so two systems in the same state have the same checksum.
+    pub fn checksum(&self) -> u64 {
+        self.body_hashes()
+            .values()
+            .chain(self.event_hashes().values())
+            .fold(0, |sum, &hash| sum.wrapping_add(hash))
+    }
+
+    /// Lists the bodies and events that disagree between `self` and `other`.
+    pub fn diff<H2: Handler>(&self, other: &System<H2>) -> Vec<Discrepancy> {
+        let mut ret = Vec::new();
+        for (id, kind) in diff_maps(&self.body_hashes(), &other.body_hashes()) {
+            ret.push(Discrepancy::Body { id, kind });
+        }
+        for (id, kind) in diff_maps(&self.event_hashes(), &other.event_hashes()) {
+            ret.push(Discrepancy::Event { id, kind });
+        }
+        ret
+    }
+
+    fn body_hashes(&self) -> BTreeMap<BodyId, u64> {
+        self.tree()
+            .dfs()
+            .map(|(body, _)| (body.id(), hash_body(self.tree(), body)))
+            .collect()
+    }
+
+    fn event_hashes(&self) -> BTreeMap<EventId, u64> {
+        self.events()
+            .map(|event| (event.id(), hash_with(|state| event.hash_content(state))))
+            .collect()
+    }
+}
+
+fn diff_maps<K: Ord + Copy>(
+    left: &BTreeMap<K, u64>,
+    right: &BTreeMap<K, u64>,
+) -> Vec<(K, DiffKind)> {
+    let mut ret = Vec::new();
+    for (&key, hash) in left {
+        match right.get(&key) {
+            None => ret.push((key, DiffKind::OnlyLeft)),
+            Some(other) if other != hash => ret.push((key, DiffKind::Different)),
+            Some(_) => (),
+        }
+    }
+    for &key in right.keys() {
+        if !left.contains_key(&key) {
+            ret.push((key, DiffKind::OnlyRight));
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Eci, Length, Mass, Time, Vector};
+
+    fn system() -> System<()> {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "mass": 1e6,
+        }))
+        .expect("Invalid schema");
+        System::from_schema(schema, ())
+    }
+
+    fn spawn(system: &mut System<()>, i: u8) -> BodyId {
+        let root = system.tree().root().id();
+        let eci = Eci::new(
+            Vector::new(1000.0 + f32::from(i) * 10.0, 0.0),
+            Vector::new(0.0, 30.0),
+        );
+        system
+            .spawn_small(root, Mass(1.0), Length(1.0), eci, Time(0))
+            .into()
+    }
+
+    #[test]
+    fn test_fnv() {
+        // the reference values of 64-bit FNV-1a
+        for &(input, expected) in &[
+            ("", 0xcbf2_9ce4_8422_2325),
+            ("a", 0xaf63_dc4c_8601_ec8c),
+            ("foobar", 0x8594_4171_f739_67e8),
+        ] {
+            assert_eq!(hash_with(|state| state.write(input.as_bytes())), expected);
+        }
+        assert_eq!(
+            hash_with(|state| state.write_usize(1)),
+            hash_with(|state| state.write_u64(1))
+        );
+    }
+
+    #[test]
+    fn test_order_independent() {
+        // each system has its own hash map keys, so the bodies are stored in different orders
+        let (mut a, mut b) = (system(), system());
+        for i in 0..20 {
+            spawn(&mut a, i);
+            spawn(&mut b, i);
+        }
+        a.schedule_timer(Time(10), 1);
+        b.schedule_timer(Time(10), 1);
+        assert_eq!(a.checksum(), b.checksum());
+        assert!(a.diff(&b).is_empty());
+
+        b.schedule_timer(Time(20), 2);
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn test_diff() {
+        let (mut a, mut b) = (system(), system());
+        let burned = spawn(&mut a, 0);
+        spawn(&mut b, 0);
+        let extra = spawn(&mut a, 1);
+        let event = b.schedule_timer(Time(10), 1);
+
+        a.burn(burned, Vector::new(1.0, 0.0), Time(0)).unwrap();
+        assert_eq!(
+            a.diff(&b),
+            vec![
+                Discrepancy::Body {
+                    id: burned,
+                    kind: DiffKind::Different
+                },
+                Discrepancy::Body {
+                    id: extra,
+                    kind: DiffKind::OnlyLeft
+                },
+                Discrepancy::Event {
+                    id: event,
+                    kind: DiffKind::OnlyRight
+                },
+            ]
+        );
+    }
+}
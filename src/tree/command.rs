@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::math::{Eci, Length, Mass, Time, Vector};
+
+/// A deferred mutation of a `System` requested by a `Handler`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    /// Adds a small body orbiting `parent` with `eci` relative to `parent`.
+    Spawn {
+        /// The ID reserved for the body when the command was issued
+        id: SmallBodyId,
+        parent: LargeBodyId,
+        mass: Mass,
+        radius: Length,
+        eci: Eci,
+    },
+    /// Removes a body and all its descendants.
+    Despawn { body: BodyId },
+    /// Changes the velocity of a body by `delta_v`.
+    Burn { body: BodyId, delta_v: Vector },
+    /// Calls `Handler::on_timer` with `tag` at `time`.
+    Schedule {
+        event: EventId,
+        time: Time,
+        tag: u64,
+    },
+    /// Removes a scheduled event from the queue.
+    Cancel { event: EventId },
+}
+
+/// The command buffer passed to `Handler` callbacks.
+///
+/// The commands are applied in the order they are issued after the callback returns.
+#[derive(Debug)]
+pub struct Commands<'s> {
+    now: Time,
+    body_ids: &'s mut IdAllocator,
+    next_event_id: &'s mut u32,
+    commands: Vec<Command>,
+}
+
+impl<'s> Commands<'s> {
+    pub(super) fn new(
+        now: Time,
+        body_ids: &'s mut IdAllocator,
+        next_event_id: &'s mut u32,
+    ) -> Self {
+        Self {
+            now,
+            body_ids,
+            next_event_id,
+            commands: Vec::new(),
+        }
+    }
+
+    /// The time of the event being handled
+    pub fn now(&self) -> Time {
+        self.now
+    }
+
+    /// Spawns a small body orbiting `parent` with `eci` relative to `parent`.
+    ///
+    /// The body is not spawned if `parent` no longer exists when the command is applied.
+    pub fn spawn(
+        &mut self,
+        parent: LargeBodyId,
+        mass: Mass,
+        radius: Length,
+        eci: Eci,
+    ) -> SmallBodyId {
+        let id = SmallBodyId(self.body_ids.alloc());
+        self.commands.push(Command::Spawn {
+            id,
+            parent,
+            mass,
+            radius,
+            eci,
+        });
+        id
+    }
+
+    pub fn despawn(&mut self, body: BodyId) {
+        self.commands.push(Command::Despawn { body });
+    }
+
+    /// Changes the velocity of `body` by `delta_v`, launching it if it has landed.
+    pub fn burn(&mut self, body: BodyId, delta_v: Vector) {
+        self.commands.push(Command::Burn { body, delta_v });
+    }
+
+    /// Schedules a call to `Handler::on_timer` with `tag` at `time`.
+    ///
+    /// Times before `now` are clamped to `now`, since the past cannot change.
+    pub fn schedule(&mut self, time: Time, tag: u64) -> EventId {
+        let time = time.max(self.now);
+        let event = EventId(next_id(self.next_event_id));
+        self.commands.push(Command::Schedule { event, time, tag });
+        event
+    }
+
+    pub fn cancel(&mut self, event: EventId) {
+        self.commands.push(Command::Cancel { event });
+    }
+
+    pub(super) fn into_commands(self) -> Vec<Command> {
+        self.commands
+    }
+}
@@ -0,0 +1,251 @@
+use super::*;
+use crate::math::Time;
+
+/// An event found by `System::detect`, ordered by the IDs of the bodies for ties
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Detected {
+    Collision(BodyId, BodyId),
+    FieldChange(BodyId, LargeBodyId, LargeBodyId),
+}
+
+impl<H: Handler> System<H> {
+    /// Advances to `t` like `advance_event`,
+    /// detecting the collisions and sphere of influence crossings of bodies on the way.
+    ///
+    /// Only the earliest detected event is scheduled at a time, just before it is due,
+    /// since the events before it may change the motion of the bodies.
+    /// The motion is sampled like in `Tree::closest_approach`,
+    /// so crossings much shorter than the sampling interval may be missed.
+    pub fn advance_detecting(&mut self, t: Time) {
+        loop {
+            let next = self.next_event().filter(|&next| next <= t);
+            match self.detect(next.unwrap_or(t)) {
+                Some((time, detected)) => {
+                    let ty = match detected {
+                        Detected::Collision(body1, body2) => {
+                            EventType::Collision(Collision { body1, body2 })
+                        }
+                        Detected::FieldChange(body, from, to) => {
+                            EventType::FieldChange(FieldChange { body, from, to })
+                        }
+                    };
+                    let id = self.next_event_id();
+                    self.schedule(Event::new(id, time, ty));
+                    self.advance_event(time);
+                }
+                None => match next {
+                    Some(next) => self.advance_event(next),
+                    None => break,
+                },
+            }
+        }
+        self.advance_event(t);
+    }
+
+    /// Finds the earliest collision or sphere of influence crossing after `now` until `until`.
+    ///
+    /// Bodies that are already in contact or in a sphere of influence at `now` are only
+    /// detected again after they have separated.
+    fn detect(&self, until: Time) -> Option<(Time, Detected)> {
+        let tree = self.tree();
+        let (from, to) = (self.now().0, until.0);
+        if to <= from {
+            return None;
+        }
+
+        let mut found = Vec::new();
+        for (body, _) in tree.dfs() {
+            let parent = match tree.parent(body.id()) {
+                Some(parent) => tree.get_large_body(parent),
+                None => continue,
+            };
+            // landed bodies move with the surface of their parent
+            let orbit = match body.orbit() {
+                Some(orbit) => orbit,
+                None => continue,
+            };
+            let period = orbit.period().unwrap_or(f64::INFINITY);
+            let height = |t: i32| orbit.position(Time(t)).norm();
+
+            let step = sample_step(period, to - from);
+            let threshold = parent.surface_radius().0 + body.radius().0;
+            if let Some(t) = first_crossing(from, to, step, threshold, height) {
+                found.push((t, Detected::Collision(body.id(), parent.id().into())));
+            }
+            if let Some(grandparent) = tree.parent(parent.id().into()) {
+                let threshold = -parent.grav_radius().0;
+                if let Some(t) = first_crossing(from, to, step, threshold, |t| -height(t)) {
+                    let detected = Detected::FieldChange(body.id(), parent.id(), grandparent);
+                    found.push((t, detected));
+                }
+            }
+
+            for sibling in parent.children() {
+                if sibling.id() == body.id() {
+                    continue;
+                }
+                let sibling_orbit = match sibling.orbit() {
+                    Some(orbit) => orbit,
+                    None => continue,
+                };
+                let period = period.min(sibling_orbit.period().unwrap_or(f64::INFINITY));
+                let step = sample_step(period, to - from);
+                let distance = |t: i32| {
+                    let t = Time(t);
+                    (orbit.position(t) - sibling_orbit.position(t)).norm()
+                };
+
+                if let BodyRef::Large(sibling) = sibling {
+                    let threshold = sibling.grav_radius().0;
+                    if let Some(t) = first_crossing(from, to, step, threshold, distance) {
+                        let detected = Detected::FieldChange(body.id(), parent.id(), sibling.id());
+                        found.push((t, detected));
+                    }
+                }
+                // each pair of siblings only collides once
+                if sibling.id() > body.id() {
+                    let threshold = body.radius().0 + sibling.radius().0;
+                    if let Some(t) = first_crossing(from, to, step, threshold, distance) {
+                        found.push((t, Detected::Collision(body.id(), sibling.id())));
+                    }
+                }
+            }
+        }
+
+        found
+            .into_iter()
+            .min()
+            .map(|(t, detected)| (Time(t), detected))
+    }
+}
+
+/// Finds the first time in `from+1..=to` at which `f` drops below `threshold`
+/// after being at or above it.
+///
+/// `f` is sampled every `step`, and local minima between the samples are refined.
+fn first_crossing(
+    from: i32,
+    to: i32,
+    step: i32,
+    threshold: f32,
+    f: impl Fn(i32) -> f32,
+) -> Option<i32> {
+    let mut samples = vec![(from, f(from))];
+    let mut t = from;
+    while t < to {
+        t = t.saturating_add(step).min(to);
+        samples.push((t, f(t)));
+    }
+
+    let inside = |t: i32| f(t) < threshold;
+    for i in 1..samples.len() {
+        let (lo, hi) = (samples[i - 1], samples[i]);
+        if lo.1 < threshold {
+            continue;
+        }
+        if hi.1 < threshold {
+            return Some(first_inside(lo.0, hi.0, inside));
+        }
+
+        // both samples are outside, but the minimum between them may be inside
+        if let Some(&next) = samples.get(i + 1) {
+            if hi.1 <= lo.1 && hi.1 <= next.1 {
+                let (min_t, min) = refine_minimum(lo.0, next.0, &f);
+                if min < threshold {
+                    return Some(first_inside(lo.0, min_t, inside));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Binary search for the first time in `lo+1..=hi` that is inside,
+/// given that `lo` is outside and `hi` is inside
+fn first_inside(mut lo: i32, mut hi: i32, inside: impl Fn(i32) -> bool) -> i32 {
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if inside(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    hi
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Eci, Length, Mass, Vector};
+
+    fn system() -> System<()> {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "mass": 1e6,
+            "children": [{
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+            }],
+        }))
+        .expect("Invalid schema");
+        System::from_schema(schema, ())
+    }
+
+    #[test]
+    fn test_first_crossing() {
+        let f = |t: i32| (t - 50).abs() as f32;
+        assert_eq!(first_crossing(0, 100, 10, 5.0, f), Some(46));
+        // the minimum between two samples outside is found
+        assert_eq!(first_crossing(0, 100, 40, 5.0, f), Some(46));
+        // already inside at the start
+        assert_eq!(first_crossing(48, 100, 10, 5.0, f), None);
+        assert_eq!(first_crossing(0, 40, 10, 5.0, f), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let mut system = system();
+        let root = system.tree().root().id();
+        let planet = system.tree().root().children()[0].borrow_large().id();
+
+        // a body falling onto the root body
+        let eci = Eci::new(Vector::new(-1000.0, 0.0), Vector::new(0.0, 1.0));
+        let falling = system.spawn_small(root, Mass(1.0), Length(1.0), eci, Time(0));
+        // a body orbiting the planet on an escape trajectory
+        let eci = Eci::new(Vector::new(50.0, 0.0), Vector::new(0.0, 8.0));
+        let escaping = system.spawn_small(planet, Mass(1.0), Length(1.0), eci, Time(0));
+        system.enable_journal();
+        system.advance_detecting(Time(500));
+
+        let journal = system.take_journal().unwrap();
+        let mut collided = false;
+        let mut escaped = false;
+        for entry in journal.entries() {
+            match entry.kind() {
+                JournalEvent::Collision { body1, body2, .. } => {
+                    assert_eq!((*body1, *body2), (falling.into(), root.into()));
+                    let height = |t: i32| {
+                        let eci = system
+                            .tree()
+                            .relative_eci(falling.into(), root.into(), Time(t));
+                        eci.unwrap().position().norm()
+                    };
+                    // the first integer time at which the bodies overlap
+                    let t = entry.time().0;
+                    assert!(height(t) < 101.0 && height(t - 1) >= 101.0);
+                    collided = true;
+                }
+                JournalEvent::FieldChange { body, from, to } => {
+                    assert_eq!((*body, *from, *to), (escaping.into(), planet, root));
+                    escaped = true;
+                }
+                kind => panic!("Unexpected event {:?}", kind),
+            }
+        }
+        assert!(collided && escaped);
+        assert_eq!(system.tree().parent(escaping.into()), Some(root));
+    }
+}
@@ -1,33 +1,83 @@
 use std::borrow::Borrow;
 use std::cmp;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 use getset::*;
+use serde::{Deserialize, Serialize};
 
 use super::*;
 use crate::math::Time;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct EventId(pub u32);
 
 #[derive(Debug)]
 pub struct Event<H: Handler> {
-    key: (EventId, Time),
+    key: (Time, EventId),
     ty: EventType<H>,
 }
 
 impl<H: Handler> Event<H> {
+    pub(super) fn new(id: EventId, time: Time, ty: EventType<H>) -> Self {
+        Self {
+            key: (time, id),
+            ty,
+        }
+    }
+
     pub fn id(&self) -> EventId {
-        self.key.0
+        self.key.1
     }
 
     pub fn time(&self) -> Time {
-        self.key.1
+        self.key.0
     }
 
     pub(super) fn into_type(self) -> EventType<H> {
         self.ty
     }
+
+    /// Hashes the time, ID and data of the event.
+    ///
+    /// Closures of `Misc` events cannot be compared, so only their time and ID are hashed.
+    pub(super) fn hash_content(&self, state: &mut impl Hasher) {
+        self.key.hash(state);
+        match &self.ty {
+            EventType::Collision(c) => (0u8, c).hash(state),
+            EventType::FieldChange(fc) => (1u8, fc).hash(state),
+            EventType::Timer(tag) => (2u8, tag).hash(state),
+            EventType::Misc(_) => 3u8.hash(state),
+        }
+    }
+
+    /// Whether the event moves the body to another field
+    pub(super) fn changes_field_of(&self, id: BodyId) -> bool {
+        match &self.ty {
+            EventType::FieldChange(fc) => fc.body == id,
+            _ => false,
+        }
+    }
+
+    /// Whether the event refers to the body
+    pub(super) fn involves(&self, id: BodyId) -> bool {
+        match &self.ty {
+            EventType::Collision(c) => c.body1 == id || c.body2 == id,
+            EventType::FieldChange(fc) => fc.body == id || fc.from.0 == id || fc.to.0 == id,
+            EventType::Timer(_) | EventType::Misc(_) => false,
+        }
+    }
+}
+
+// not derived because `H` does not need to be `Clone`
+impl<H: Handler> Clone for Event<H> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key,
+            ty: self.ty.clone(),
+        }
+    }
 }
 
 impl<H: Handler> PartialEq for Event<H> {
@@ -46,22 +96,38 @@ impl<H: Handler> PartialOrd for Event<H> {
 
 impl<H: Handler> Ord for Event<H> {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.time()
-            .cmp(&other.time())
-            .then_with(|| self.id().cmp(&other.id()))
+        self.key.cmp(&other.key)
     }
 }
 
-impl<H: Handler> Borrow<(EventId, Time)> for Event<H> {
-    fn borrow(&self) -> &(EventId, Time) {
+// the key must be ordered the same way as the event for lookups in the queue
+impl<H: Handler> Borrow<(Time, EventId)> for Event<H> {
+    fn borrow(&self) -> &(Time, EventId) {
         &self.key
     }
 }
 
+/// A closure run by a `Misc` event
+pub(super) type MiscFn<H> = Rc<dyn Fn(&mut System<H>)>;
+
 pub(super) enum EventType<H: Handler> {
     Collision(Collision),
     FieldChange(FieldChange),
-    Misc(Box<dyn FnOnce(&mut System<H>)>),
+    /// Calls `Handler::on_timer` with the tag
+    Timer(u64),
+    /// Runs the closure, which is shared with the snapshots of the system
+    Misc(MiscFn<H>),
+}
+
+impl<H: Handler> Clone for EventType<H> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Collision(c) => Self::Collision(c.clone()),
+            Self::FieldChange(fc) => Self::FieldChange(fc.clone()),
+            Self::Timer(tag) => Self::Timer(*tag),
+            Self::Misc(f) => Self::Misc(Rc::clone(f)),
+        }
+    }
 }
 
 impl<H: Handler> fmt::Debug for EventType<H> {
@@ -69,12 +135,13 @@ impl<H: Handler> fmt::Debug for EventType<H> {
         match self {
             Self::Collision(c) => write!(f, "Collision({:?})", c),
             Self::FieldChange(fc) => write!(f, "FieldChange({:?})", fc),
+            Self::Timer(tag) => write!(f, "Timer({})", tag),
             Self::Misc(_) => write!(f, "Misc(Fn)"),
         }
     }
 }
 
-#[derive(Debug, CopyGetters)]
+#[derive(Debug, Clone, Hash, CopyGetters)]
 pub(super) struct Collision {
     #[get_copy = "pub(super)"]
     pub(super) body1: BodyId,
@@ -82,7 +149,7 @@ pub(super) struct Collision {
     pub(super) body2: BodyId,
 }
 
-#[derive(Debug, CopyGetters)]
+#[derive(Debug, Clone, Hash, CopyGetters)]
 pub(super) struct FieldChange {
     #[get_copy = "pub(super)"]
     pub(super) body: BodyId,
@@ -0,0 +1,178 @@
+use super::*;
+use crate::math::{Eci, Time};
+
+impl Tree {
+    /// Returns the ECI of a body relative to its parent,
+    /// or `None` if the body has been removed.
+    ///
+    /// The root body is considered to be at rest at its own origin.
+    pub fn eci_in_parent(&self, id: BodyId, t: Time) -> Option<Eci> {
+        let eci = match self.try_get_body(id)? {
+            BodyRef::Large(body) => match body.orbit() {
+                Some(orbit) => orbit.eci(t),
+                None => Eci::zero(),
+            },
+            BodyRef::Small(body) => {
+                let parent = self.parent(id).expect("Small bodies have a parent");
+                body.eci_in(self.get_large_body(parent), t)
+            }
+        };
+        Some(eci)
+    }
+
+    /// Returns the ECI of a body relative to the root body,
+    /// or `None` if the body has been removed.
+    pub fn absolute_eci(&self, id: BodyId, t: Time) -> Option<Eci> {
+        self.relative_eci(id, self.root().id().into(), t)
+    }
+
+    /// Returns the ECI of `id` relative to `origin`.
+    ///
+    /// The transforms are only composed up to the closest common ancestor of the two bodies, so
+    /// nearby bodies deep in the tree do not lose precision from large offsets near the root.
+    ///
+    /// Returns `None` if either body has been removed.
+    pub fn relative_eci(&self, id: BodyId, origin: BodyId, t: Time) -> Option<Eci> {
+        let (id_path, origin_path) = self.paths_to_common_ancestor(id, origin)?;
+
+        let mut eci = Eci::zero();
+        for &body in &id_path {
+            eci = eci + self.eci_in_parent(body, t)?;
+        }
+        for &body in &origin_path {
+            eci = eci - self.eci_in_parent(body, t)?;
+        }
+        Some(eci)
+    }
+
+    /// Returns the bodies from `a` and `b` up to, but excluding, their closest common ancestor.
+    ///
+    /// A body is considered to be its own ancestor,
+    /// so one of the paths is empty if `a` is an ancestor of `b` or vice versa.
+    /// Returns `None` if either body has been removed.
+    pub(super) fn paths_to_common_ancestor(
+        &self,
+        a: BodyId,
+        b: BodyId,
+    ) -> Option<(Vec<BodyId>, Vec<BodyId>)> {
+        if !self.contains(a) || !self.contains(b) {
+            return None;
+        }
+
+        let mut b_path = vec![b];
+        b_path.extend(self.ancestors(b).map(|(body, _)| body.id()));
+
+        let mut a_path = Vec::new();
+        let mut ancestor = a;
+        while !b_path.contains(&ancestor) {
+            a_path.push(ancestor);
+            ancestor = self
+                .parent(ancestor)
+                .expect("The root body is an ancestor of every body")
+                .into();
+        }
+
+        let common = b_path
+            .iter()
+            .position(|&body| body == ancestor)
+            .expect("Loop ends when ancestor is in b_path");
+        b_path.truncate(common);
+        Some((a_path, b_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Length, Mass, Vector};
+
+    fn system() -> System<()> {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "mass": 1e6,
+            "children": [
+                {
+                    "surface_radius": 10.0,
+                    "grav_radius": 300.0,
+                    "mass": 1000.0,
+                    "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+                    "children": [{
+                        "surface_radius": 1.0,
+                        "grav_radius": 20.0,
+                        "mass": 1.0,
+                        "eci": {"position": [50.0, 0.0], "velocity": [0.0, 4.47]},
+                    }],
+                },
+                {
+                    "surface_radius": 10.0,
+                    "grav_radius": 300.0,
+                    "mass": 1000.0,
+                    "eci": {"position": [0.0, -3000.0], "velocity": [18.26, 0.0]},
+                },
+            ],
+        }))
+        .expect("Invalid schema");
+        System::from_schema(schema, ())
+    }
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).norm() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_relative_eci() {
+        let system = system();
+        let tree = system.tree();
+        let t = Time(123);
+        let planets = tree.root().children();
+        let (planet, other) = (planets[0].id(), planets[1].id());
+        let moon = planets[0].borrow_large().children()[0].id();
+
+        let planet_eci = tree.absolute_eci(planet, t).unwrap();
+        let moon_in_planet = tree.eci_in_parent(moon, t).unwrap();
+        let moon_eci = tree.absolute_eci(moon, t).unwrap();
+        assert_close(
+            moon_eci.position(),
+            planet_eci.position() + moon_in_planet.position(),
+        );
+        assert_close(
+            moon_eci.velocity(),
+            planet_eci.velocity() + moon_in_planet.velocity(),
+        );
+
+        let forward = tree.relative_eci(moon, other, t).unwrap();
+        let backward = tree.relative_eci(other, moon, t).unwrap();
+        assert_close(forward.position(), -backward.position());
+        assert_close(forward.velocity(), -backward.velocity());
+
+        let other_eci = tree.absolute_eci(other, t).unwrap();
+        assert_close(
+            forward.position(),
+            moon_eci.position() - other_eci.position(),
+        );
+        assert_close(
+            tree.relative_eci(moon, moon, t).unwrap().position(),
+            Vector::zeros(),
+        );
+    }
+
+    #[test]
+    fn test_removed() {
+        let mut system = system();
+        let root = system.tree().root().id();
+        let planet = system.tree().root().children()[0].id();
+        let eci = Eci::new(Vector::new(500.0, 0.0), Vector::new(0.0, 44.0));
+        let small = system.spawn_small(root, Mass(1.0), Length(1.0), eci, Time(0));
+        system.remove_body(small.into());
+        system.remove_body(planet);
+
+        let tree = system.tree();
+        for &id in &[small.into(), planet] {
+            assert!(tree.eci_in_parent(id, Time(0)).is_none());
+            assert!(tree.absolute_eci(id, Time(0)).is_none());
+            assert!(tree.relative_eci(id, root.into(), Time(0)).is_none());
+            assert!(tree.relative_eci(root.into(), id, Time(0)).is_none());
+            assert!(tree.paths_to_common_ancestor(id, root.into()).is_none());
+        }
+    }
+}
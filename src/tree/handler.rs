@@ -1,24 +1,164 @@
 use super::*;
 use crate::math::{Length, Mass, Vector};
 
+/// Game logic reacting to the events of a `System`.
+///
+/// Each callback receives a `Commands` buffer,
+/// which is applied to the system after the callback returns.
+/// All callbacks do nothing by default.
+///
+/// Handlers can be composed as a tuple, e.g. `(Physics, Scoring, Logger)`,
+/// which passes each event to every handler in order.
 pub trait Handler: Sized {
+    /// Decides how the colliding bodies are resolved.
+    ///
+    /// Returns `None` if the handler has no opinion,
+    /// in which case the collision has no effect unless another handler resolves it.
     fn on_collision(
         &mut self,
-        body1: BodyRef<'_>,
-        body2: BodyRef<'_>,
-    ) -> (CollisionResolution, CollisionResolution);
+        _body1: BodyRef<'_>,
+        _body2: BodyRef<'_>,
+        _commands: &mut Commands<'_>,
+    ) -> Option<(CollisionResolution, CollisionResolution)> {
+        None
+    }
 
-    fn on_enter_subfield(&mut self, body: BodyRef<'_>, from: &LargeBody, to: &LargeBody);
-    fn on_exit_subfield(&mut self, body: BodyRef<'_>, from: &LargeBody, to: &LargeBody);
+    /// Called after `body` has moved from `from` to its child `to`.
+    fn on_enter_subfield(
+        &mut self,
+        _body: BodyRef<'_>,
+        _from: &LargeBody,
+        _to: &LargeBody,
+        _commands: &mut Commands<'_>,
+    ) {
+    }
+    /// Called after `body` has moved from `from` to its parent `to`.
+    fn on_exit_subfield(
+        &mut self,
+        _body: BodyRef<'_>,
+        _from: &LargeBody,
+        _to: &LargeBody,
+        _commands: &mut Commands<'_>,
+    ) {
+    }
+
+    /// Called when an event scheduled with `Commands::schedule` is due.
+    fn on_timer(&mut self, _tree: &Tree, _tag: u64, _commands: &mut Commands<'_>) {}
 }
 
+macro_rules! tuple_handler {
+    ($($name:ident),+) => {
+        /// Passes each event to the handlers in order.
+        ///
+        /// The first collision resolution returned wins,
+        /// but the later handlers still see the collision.
+        #[allow(non_snake_case)]
+        impl<$($name: Handler),+> Handler for ($($name,)+) {
+            fn on_collision(
+                &mut self,
+                body1: BodyRef<'_>,
+                body2: BodyRef<'_>,
+                commands: &mut Commands<'_>,
+            ) -> Option<(CollisionResolution, CollisionResolution)> {
+                let ($($name,)+) = self;
+                let mut resolution = None;
+                $(
+                    let ret = $name.on_collision(body1, body2, commands);
+                    resolution = resolution.or(ret);
+                )+
+                resolution
+            }
+
+            fn on_enter_subfield(
+                &mut self,
+                body: BodyRef<'_>,
+                from: &LargeBody,
+                to: &LargeBody,
+                commands: &mut Commands<'_>,
+            ) {
+                let ($($name,)+) = self;
+                $($name.on_enter_subfield(body, from, to, commands);)+
+            }
+
+            fn on_exit_subfield(
+                &mut self,
+                body: BodyRef<'_>,
+                from: &LargeBody,
+                to: &LargeBody,
+                commands: &mut Commands<'_>,
+            ) {
+                let ($($name,)+) = self;
+                $($name.on_exit_subfield(body, from, to, commands);)+
+            }
+
+            fn on_timer(&mut self, tree: &Tree, tag: u64, commands: &mut Commands<'_>) {
+                let ($($name,)+) = self;
+                $($name.on_timer(tree, tag, commands);)+
+            }
+        }
+    };
+}
+tuple_handler!(A);
+tuple_handler!(A, B);
+tuple_handler!(A, B, C);
+tuple_handler!(A, B, C, D);
+tuple_handler!(A, B, C, D, E);
+tuple_handler!(A, B, C, D, E, F);
+
+impl Handler for () {}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum CollisionResolution {
     Remove,
     Mutate(BodyMutation),
+    /// Rests a small body on the surface of its parent.
+    ///
+    /// The body is left unchanged unless the other body is its parent.
+    /// Large bodies cannot land, so they are left unchanged.
+    Land,
+    /// Merges the other body into this body, which survives with the total mass.
+    ///
+    /// The resolution for the other body is ignored.
+    /// If both bodies return `Merge`, the first body survives.
+    Merge {
+        /// The new radius of this body, which is the `surface_radius` for large bodies
+        radius: Length,
+    },
+    /// Resolves this body with `collider` and spawns `debris` around the impact point.
+    Fragment {
+        collider: Box<CollisionResolution>,
+        debris: Vec<Debris>,
+    },
+}
+
+impl CollisionResolution {
+    /// Separates the debris from the resolution of the collider.
+    pub(super) fn into_parts(self) -> (Self, Vec<Debris>) {
+        match self {
+            Self::Fragment { collider, debris } => {
+                let (collider, mut inner) = collider.into_parts();
+                inner.extend(debris);
+                (collider, inner)
+            }
+            resolution => (resolution, Vec::new()),
+        }
+    }
+}
+
+/// A small body spawned by a collision
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Debris {
+    pub mass: Mass,
+    pub radius: Length,
+    /// The position relative to the impact point
+    pub offset: Vector,
+    /// The velocity relative to the center of mass of the colliding bodies
+    pub velocity: Vector,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct BodyMutation {
+    /// The new radius of the body, which is the `radius` for small bodies
     pub surface_radius: Option<Length>,
     pub grav_radius: Option<Length>,
     pub mass: Option<Mass>,
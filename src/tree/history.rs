@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use super::*;
+use crate::math::Time;
+
+/// The snapshots of a `System` and the commands issued to it since the history was enabled
+#[derive(Debug)]
+pub(super) struct History<H: Handler> {
+    /// The minimum time between two snapshots
+    pub(super) interval: i32,
+    /// Snapshots in increasing order of time
+    pub(super) snapshots: Vec<Snapshot<H>>,
+    /// The commands issued through `System::issue` with the time they were issued
+    pub(super) inputs: Vec<(Time, Vec<Command>)>,
+    /// The number of `inputs` applied to the current state
+    pub(super) cursor: usize,
+    /// Clones the handler, because `Handler` does not require `Clone`
+    pub(super) clone_handler: fn(&H) -> H,
+}
+
+impl<H: Handler> History<H> {
+    /// Discards the snapshots and inputs that are no longer reachable
+    /// after a command is issued at `now` following a seek.
+    pub(super) fn truncate(&mut self, now: Time) {
+        let cursor = self.cursor;
+        self.inputs.truncate(cursor);
+        self.snapshots
+            .retain(|snapshot| snapshot.now <= now && snapshot.inputs_len <= cursor);
+    }
+
+    /// Returns the latest snapshot at or before `t`.
+    pub(super) fn snapshot_before(&self, t: Time) -> Option<&Snapshot<H>> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.now <= t)
+    }
+}
+
+/// The state of a `System` at `now`
+#[derive(Debug)]
+pub(super) struct Snapshot<H: Handler> {
+    pub(super) now: Time,
+    pub(super) next_event_id: u32,
+    pub(super) body_ids: IdAllocator,
+    pub(super) tree: Tree,
+    pub(super) event_queue: BTreeSet<Event<H>>,
+    pub(super) handler: H,
+    /// The number of inputs issued before the snapshot
+    pub(super) inputs_len: usize,
+    /// The number of journal entries before the snapshot
+    pub(super) journal_len: usize,
+}
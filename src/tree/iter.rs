@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use std::iter;
+
+use super::*;
+
+/// Iterates over the bodies in a subtree in depth-first pre-order.
+///
+/// Yields each body with its depth in the tree, where the root body has depth 0.
+#[derive(Debug)]
+pub struct Dfs<'t> {
+    stack: Vec<(BodyRef<'t>, usize)>,
+}
+
+impl<'t> Iterator for Dfs<'t> {
+    type Item = (BodyRef<'t>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (body, depth) = self.stack.pop()?;
+        if let BodyRef::Large(body) = body {
+            // pushed in reverse so that children are visited in the order of their IDs
+            let children = body.children().into_iter().rev();
+            self.stack.extend(children.map(|child| (child, depth + 1)));
+        }
+        Some((body, depth))
+    }
+}
+
+/// Iterates over the bodies in a subtree in breadth-first order.
+///
+/// Yields each body with its depth in the tree, where the root body has depth 0.
+#[derive(Debug)]
+pub struct Bfs<'t> {
+    queue: VecDeque<(BodyRef<'t>, usize)>,
+}
+
+impl<'t> Iterator for Bfs<'t> {
+    type Item = (BodyRef<'t>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (body, depth) = self.queue.pop_front()?;
+        if let BodyRef::Large(body) = body {
+            let children = body.children().into_iter();
+            self.queue.extend(children.map(|child| (child, depth + 1)));
+        }
+        Some((body, depth))
+    }
+}
+
+/// Iterates over the ancestors of a body, from its parent up to the root body.
+///
+/// Yields each ancestor with its depth in the tree, where the root body has depth 0.
+#[derive(Debug)]
+pub struct Ancestors<'t> {
+    tree: &'t Tree,
+    next: Option<LargeBodyId>,
+    depth: usize,
+}
+
+impl<'t> Iterator for Ancestors<'t> {
+    type Item = (BodyRef<'t>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = self.tree.parent(id.into());
+        self.depth = self.depth.saturating_sub(1);
+        Some((BodyRef::Large(self.tree.get_large_body(id)), self.depth))
+    }
+}
+
+impl LargeBody {
+    /// Returns the direct children of this body, sorted by their IDs.
+    pub fn children(&self) -> Vec<BodyRef<'_>> {
+        let mut children = self
+            .large
+            .values()
+            .map(BodyRef::Large)
+            .chain(self.small.values().map(BodyRef::Small))
+            .collect::<Vec<_>>();
+        children.sort_by_key(|child| child.id());
+        children
+    }
+}
+
+impl Tree {
+    /// Returns the number of ancestors of the body.
+    pub fn depth(&self, id: BodyId) -> usize {
+        iter::successors(self.parent(id), |&parent| self.parent(parent.into())).count()
+    }
+
+    /// Iterates over all bodies in the tree in depth-first pre-order.
+    pub fn dfs(&self) -> Dfs<'_> {
+        Dfs {
+            stack: vec![(BodyRef::Large(self.root()), 0)],
+        }
+    }
+
+    /// Iterates over all bodies in the tree in breadth-first order.
+    pub fn bfs(&self) -> Bfs<'_> {
+        let mut queue = VecDeque::new();
+        queue.push_back((BodyRef::Large(self.root()), 0));
+        Bfs { queue }
+    }
+
+    /// Iterates over the ancestors of the body, starting from its parent.
+    pub fn ancestors(&self, id: BodyId) -> Ancestors<'_> {
+        Ancestors {
+            tree: self,
+            next: self.parent(id),
+            depth: self.depth(id),
+        }
+    }
+
+    /// Iterates over all descendants of the body in depth-first pre-order,
+    /// excluding the body itself.
+    ///
+    /// Yields nothing if the body is not in the tree.
+    pub fn descendants(&self, id: LargeBodyId) -> Dfs<'_> {
+        let depth = self.depth(id.into()) + 1;
+        let children = match self.try_get_large_body(id) {
+            Some(body) => body.children(),
+            None => Vec::new(),
+        };
+        Dfs {
+            stack: children
+                .into_iter()
+                .rev()
+                .map(|child| (child, depth))
+                .collect(),
+        }
+    }
+
+    /// Iterates over the other children of the parent of the body.
+    ///
+    /// The root body has no siblings.
+    pub fn siblings(&self, id: BodyId) -> impl Iterator<Item = (BodyRef<'_>, usize)> {
+        let depth = self.depth(id);
+        let siblings = match self.parent(id) {
+            Some(parent) => self.get_large_body(parent).children(),
+            None => Vec::new(),
+        };
+        siblings
+            .into_iter()
+            .filter(move |sibling| sibling.id() != id)
+            .map(move |sibling| (sibling, depth))
+    }
+
+    /// Iterates over all large bodies in the tree in depth-first pre-order.
+    pub fn large_bodies(&self) -> impl Iterator<Item = (BodyRef<'_>, usize)> {
+        self.dfs()
+            .filter(|(body, _)| matches!(body, BodyRef::Large(_)))
+    }
+
+    /// Iterates over all small bodies in the tree in depth-first pre-order.
+    pub fn small_bodies(&self) -> impl Iterator<Item = (BodyRef<'_>, usize)> {
+        self.dfs()
+            .filter(|(body, _)| matches!(body, BodyRef::Small(_)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Eci, Length, Mass, Time, Vector};
+
+    /// The bodies of a tree of 4 levels
+    struct Bodies {
+        root: BodyId,
+        planet1: BodyId,
+        planet2: BodyId,
+        moon: BodyId,
+        /// Small bodies orbiting the root, the first planet and the moon
+        small_root: BodyId,
+        small_planet: BodyId,
+        small_moon: BodyId,
+    }
+
+    fn system() -> (System<()>, Bodies) {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "mass": 1e6,
+            "children": [{
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+                "children": [{
+                    "surface_radius": 2.0,
+                    "grav_radius": 20.0,
+                    "mass": 10.0,
+                    "eci": {"position": [100.0, 0.0], "velocity": [0.0, 3.16]},
+                }],
+            }, {
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [-3000.0, 0.0], "velocity": [0.0, -18.26]},
+            }],
+        }))
+        .expect("Invalid schema");
+        let mut system = System::from_schema(schema, ());
+
+        let root = system.tree().root();
+        let children = root.children();
+        let (root, planet1, planet2) = (root.id(), children[0].id(), children[1].id());
+        let moon = system
+            .tree()
+            .get_large_body(LargeBodyId(planet1))
+            .children()[0]
+            .id();
+
+        let mut spawn = |parent: BodyId| {
+            let eci = Eci::new(Vector::new(5.0, 0.0), Vector::new(0.0, 1.0));
+            let parent = LargeBodyId(parent);
+            let id = system.spawn_small(parent, Mass(1.0), Length(1.0), eci, Time(0));
+            id.into()
+        };
+        let bodies = Bodies {
+            root: root.into(),
+            planet1,
+            planet2,
+            moon,
+            small_root: spawn(root.into()),
+            small_planet: spawn(planet1),
+            small_moon: spawn(moon),
+        };
+        (system, bodies)
+    }
+
+    fn ids<'t>(iter: impl Iterator<Item = (BodyRef<'t>, usize)>) -> Vec<(BodyId, usize)> {
+        iter.map(|(body, depth)| (body.id(), depth)).collect()
+    }
+
+    #[test]
+    fn test_traversal() {
+        let (system, b) = system();
+        let tree = system.tree();
+
+        let dfs = vec![
+            (b.root, 0),
+            (b.planet1, 1),
+            (b.moon, 2),
+            (b.small_moon, 3),
+            (b.small_planet, 2),
+            (b.planet2, 1),
+            (b.small_root, 1),
+        ];
+        assert_eq!(ids(tree.dfs()), dfs);
+        let bfs = vec![
+            (b.root, 0),
+            (b.planet1, 1),
+            (b.planet2, 1),
+            (b.small_root, 1),
+            (b.moon, 2),
+            (b.small_planet, 2),
+            (b.small_moon, 3),
+        ];
+        assert_eq!(ids(tree.bfs()), bfs);
+
+        let large = vec![(b.root, 0), (b.planet1, 1), (b.moon, 2), (b.planet2, 1)];
+        assert_eq!(ids(tree.large_bodies()), large);
+        let small = vec![(b.small_moon, 3), (b.small_planet, 2), (b.small_root, 1)];
+        assert_eq!(ids(tree.small_bodies()), small);
+    }
+
+    #[test]
+    fn test_relatives() {
+        let (system, b) = system();
+        let tree = system.tree();
+
+        assert_eq!(tree.depth(b.root), 0);
+        assert_eq!(tree.depth(b.small_moon), 3);
+        let ancestors = vec![(b.moon, 2), (b.planet1, 1), (b.root, 0)];
+        assert_eq!(ids(tree.ancestors(b.small_moon)), ancestors);
+        assert_eq!(ids(tree.ancestors(b.root)), vec![]);
+
+        let descendants = vec![(b.moon, 2), (b.small_moon, 3), (b.small_planet, 2)];
+        assert_eq!(ids(tree.descendants(LargeBodyId(b.planet1))), descendants);
+        assert_eq!(ids(tree.descendants(LargeBodyId(b.planet2))), vec![]);
+
+        let siblings = vec![(b.planet1, 1), (b.small_root, 1)];
+        assert_eq!(ids(tree.siblings(b.planet2)), siblings);
+        assert_eq!(ids(tree.siblings(b.small_moon)), vec![]);
+        assert_eq!(ids(tree.siblings(b.root)), vec![]);
+    }
+
+    #[test]
+    fn test_removed() {
+        let (mut system, b) = system();
+        system.remove_body(b.planet1);
+        let tree = system.tree();
+
+        assert_eq!(ids(tree.descendants(LargeBodyId(b.planet1))), vec![]);
+        assert_eq!(ids(tree.descendants(LargeBodyId(b.moon))), vec![]);
+        assert_eq!(ids(tree.ancestors(b.small_moon)), vec![]);
+        assert_eq!(ids(tree.siblings(b.moon)), vec![]);
+        assert_eq!(tree.depth(b.small_moon), 0);
+        let dfs = vec![(b.root, 0), (b.planet2, 1), (b.small_root, 1)];
+        assert_eq!(ids(tree.dfs()), dfs);
+    }
+}
@@ -0,0 +1,107 @@
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::math::Time;
+
+/// A record of the events executed by a `System`
+#[derive(Debug, Clone, Default, Getters, Serialize, Deserialize)]
+pub struct Journal {
+    #[get = "pub"]
+    entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    pub(super) fn push(&mut self, entry: JournalEntry) {
+        self.entries.push(entry);
+    }
+
+    pub(super) fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+}
+
+#[derive(Debug, Clone, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[get_copy = "pub"]
+    time: Time,
+    #[get_copy = "pub"]
+    event: EventId,
+    #[get = "pub"]
+    kind: JournalEvent,
+    /// The commands issued by the handler, in the order they were applied
+    ///
+    /// The debris of a collision is recorded as `Spawn` commands before the handler commands.
+    #[get = "pub"]
+    commands: Vec<Command>,
+    /// The motion of each involved body after the event
+    ///
+    /// The motion is `None` if the body has been removed or is the root body.
+    #[get = "pub"]
+    outcomes: Vec<(BodyId, Option<Motion>)>,
+}
+
+impl JournalEntry {
+    pub(super) fn new(
+        tree: &Tree,
+        time: Time,
+        event: EventId,
+        kind: JournalEvent,
+        commands: Vec<Command>,
+    ) -> Self {
+        let outcomes = kind
+            .bodies()
+            .into_iter()
+            .map(|id| {
+                let motion = tree.try_get_body(id).and_then(|body| match body {
+                    BodyRef::Large(body) => body.orbit().clone().map(Motion::Orbiting),
+                    BodyRef::Small(body) => Some(body.motion().clone()),
+                });
+                (id, motion)
+            })
+            .collect();
+        Self {
+            time,
+            event,
+            kind,
+            commands,
+            outcomes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JournalEvent {
+    Collision {
+        body1: BodyId,
+        body2: BodyId,
+        /// The resolutions returned by the handler, if any
+        resolution: Option<(CollisionResolution, CollisionResolution)>,
+    },
+    FieldChange {
+        body: BodyId,
+        from: LargeBodyId,
+        to: LargeBodyId,
+    },
+    Timer {
+        tag: u64,
+    },
+    /// An event scheduled with a closure, which cannot be inspected
+    Misc,
+    /// A collision or field change that was skipped because some of its bodies were removed
+    Skipped {
+        bodies: Vec<BodyId>,
+    },
+}
+
+impl JournalEvent {
+    /// The bodies involved in the event
+    pub fn bodies(&self) -> Vec<BodyId> {
+        match self {
+            Self::Collision { body1, body2, .. } => vec![*body1, *body2],
+            Self::FieldChange { body, .. } => vec![*body],
+            Self::Skipped { bodies } => bodies.clone(),
+            Self::Timer { .. } | Self::Misc => Vec::new(),
+        }
+    }
+}
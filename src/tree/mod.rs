@@ -1,11 +1,39 @@
+mod approach;
+pub use approach::*;
+
 mod body;
 pub use body::*;
 
+mod checksum;
+pub use checksum::*;
+
+mod command;
+pub use command::*;
+
+mod detect;
+
 mod event;
 pub use event::*;
 
+mod frame;
+
+mod history;
+use history::*;
+
+mod iter;
+pub use iter::*;
+
+mod journal;
+pub use journal::*;
+
+mod schema;
+pub use schema::*;
+
 mod system;
 pub use system::*;
 
+mod transfer;
+pub use transfer::*;
+
 mod handler;
 pub use handler::*;
@@ -0,0 +1,251 @@
+use std::fmt;
+
+use super::*;
+use crate::math::Vector;
+
+/// A format from which a `LargeBodySchema` can be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFormat {
+    Json,
+    Ron,
+    Toml,
+}
+
+impl SchemaFormat {
+    /// Returns the format of files with the extension, if any.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "ron" => Some(Self::Ron),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// An error when loading a `LargeBodySchema`
+#[derive(Debug)]
+pub struct SchemaError {
+    /// The path to the offending field, e.g. `children[0].eci`,
+    /// or an empty string if the error is not about a field
+    path: String,
+    message: String,
+}
+
+impl SchemaError {
+    fn from_message(message: impl fmt::Display) -> Self {
+        Self {
+            path: String::new(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl LargeBodySchema {
+    /// Parses and validates a schema.
+    ///
+    /// The `grav_radius` of the root body may be omitted, since it is always infinite.
+    pub fn load(source: &[u8], format: SchemaFormat) -> Result<Self, SchemaError> {
+        let schema: Self = match format {
+            SchemaFormat::Json => {
+                let mut de = serde_json::Deserializer::from_slice(source);
+                let schema = deserialize(&mut de)?;
+                de.end().map_err(SchemaError::from_message)?;
+                schema
+            }
+            SchemaFormat::Ron => {
+                let mut de =
+                    ron::de::Deserializer::from_bytes(source).map_err(SchemaError::from_message)?;
+                let schema = deserialize(&mut de)?;
+                de.end().map_err(SchemaError::from_message)?;
+                schema
+            }
+            SchemaFormat::Toml => {
+                let source = std::str::from_utf8(source).map_err(SchemaError::from_message)?;
+                // the TOML deserializer always parses the whole document
+                deserialize(&mut toml::Deserializer::new(source))?
+            }
+        };
+        schema.validate(String::new())?;
+        Ok(schema)
+    }
+
+    fn validate(&self, path: String) -> Result<(), SchemaError> {
+        let field = |name: &str| {
+            if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", path, name)
+            }
+        };
+        let positive = |value: f32, name: &str| {
+            if value > 0.0 {
+                Ok(())
+            } else {
+                Err(SchemaError {
+                    path: field(name),
+                    message: format!("must be positive, got {}", value),
+                })
+            }
+        };
+        let finite = |value: Vector, name: &str| {
+            if value.iter().all(|x| x.is_finite()) {
+                Ok(())
+            } else {
+                Err(SchemaError {
+                    path: field(name),
+                    message: format!("must be finite, got {}", value),
+                })
+            }
+        };
+        positive(self.surface_radius().0, "surface_radius")?;
+        positive(self.mass().0, "mass")?;
+
+        if let Some(eci) = self.eci() {
+            finite(eci.position(), "eci.position")?;
+            finite(eci.velocity(), "eci.velocity")?;
+        }
+
+        // the root body has no parent to orbit and its sphere of influence is ignored
+        if !path.is_empty() {
+            if self.eci().is_none() {
+                return Err(SchemaError {
+                    path: field("eci"),
+                    message: "child bodies must have an ECI".to_string(),
+                });
+            }
+            if !self.grav_radius().0.is_finite() {
+                return Err(SchemaError {
+                    path: field("grav_radius"),
+                    message: "child bodies must have a finite sphere of influence".to_string(),
+                });
+            }
+            positive(self.grav_radius().0, "grav_radius")?;
+        }
+
+        for (i, child) in self.children().iter().enumerate() {
+            child.validate(format!("{}[{}]", field("children"), i))?;
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes a schema, tracking the path to the field that fails.
+fn deserialize<'de, D>(de: D) -> Result<LargeBodySchema, SchemaError>
+where
+    D: serde::Deserializer<'de>,
+    D::Error: fmt::Display,
+{
+    serde_path_to_error::deserialize(de).map_err(|err| {
+        let path = err.path().to_string();
+        SchemaError {
+            // the path of the root is "."
+            path: if path == "." { String::new() } else { path },
+            message: err.into_inner().to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "surface_radius": 1000.0,
+        "mass": 1000000.0,
+        "children": [{
+            "surface_radius": 100.0,
+            "grav_radius": 500.0,
+            "mass": 1000.0,
+            "eci": {"position": [3000.0, 0.0], "velocity": [0.0, 18.26]}
+        }]
+    }"#;
+
+    const RON: &str = r#"(
+        surface_radius: 1000.0,
+        mass: 1000000.0,
+        children: [(
+            surface_radius: 100.0,
+            grav_radius: 500.0,
+            mass: 1000.0,
+            eci: Some((position: [3000.0, 0.0], velocity: [0.0, 18.26])),
+        )],
+    )"#;
+
+    const TOML: &str = r#"
+        surface_radius = 1000.0
+        mass = 1000000.0
+
+        [[children]]
+        surface_radius = 100.0
+        grav_radius = 500.0
+        mass = 1000.0
+        eci = {position = [3000.0, 0.0], velocity = [0.0, 18.26]}
+    "#;
+
+    #[test]
+    fn test_formats() {
+        for &(source, format) in &[
+            (JSON, SchemaFormat::Json),
+            (RON, SchemaFormat::Ron),
+            (TOML, SchemaFormat::Toml),
+        ] {
+            let schema = LargeBodySchema::load(source.as_bytes(), format)
+                .unwrap_or_else(|err| panic!("{:?}: {}", format, err));
+            assert!(schema.grav_radius().0.is_infinite());
+            let child = &schema.children()[0];
+            assert_eq!(child.grav_radius().0, 500.0);
+            assert_eq!(child.eci().as_ref().unwrap().velocity().y, 18.26);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = JSON.replace(r#""mass": 1000.0"#, r#""mass": "heavy""#);
+        let err = LargeBodySchema::load(source.as_bytes(), SchemaFormat::Json).unwrap_err();
+        assert_eq!(err.path(), "children[0].mass");
+
+        let source = TOML.replace("grav_radius = 500.0", "");
+        let err = LargeBodySchema::load(source.as_bytes(), SchemaFormat::Toml).unwrap_err();
+        assert_eq!(err.path(), "children[0].grav_radius");
+
+        let source = RON.replace("grav_radius: 500.0", "grav_radius: -500.0");
+        let err = LargeBodySchema::load(source.as_bytes(), SchemaFormat::Ron).unwrap_err();
+        assert_eq!(err.path(), "children[0].grav_radius");
+
+        let source = TOML.replace("position = [3000.0, 0.0]", "position = [nan, 0.0]");
+        let err = LargeBodySchema::load(source.as_bytes(), SchemaFormat::Toml).unwrap_err();
+        assert_eq!(err.path(), "children[0].eci.position");
+
+        let source = TOML.replace("velocity = [0.0, 18.26]", "velocity = [0.0, inf]");
+        let err = LargeBodySchema::load(source.as_bytes(), SchemaFormat::Toml).unwrap_err();
+        assert_eq!(err.path(), "children[0].eci.velocity");
+
+        for period in &["0.0", "inf", "nan"] {
+            let rotation = format!("rotation = {{period = {}}}\n        [[children]]", period);
+            let source = TOML.replace("[[children]]", &rotation);
+            let err = LargeBodySchema::load(source.as_bytes(), SchemaFormat::Toml).unwrap_err();
+            assert_eq!(err.path(), "rotation", "{}", err);
+        }
+    }
+}
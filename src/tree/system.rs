@@ -4,21 +4,29 @@ use std::f32;
 use getset::*;
 
 use super::*;
-use crate::math::{Length, Mass, Orbit, Time};
+use crate::math::{Eci, Length, Mass, Orbit, Time, Vector};
 
-#[derive(Debug, Getters)]
+#[derive(Debug, Getters, CopyGetters)]
 pub struct System<H: Handler> {
+    /// The time of the last executed event or `advance_event` call
+    #[get_copy = "pub"]
+    now: Time,
     next_event_id: u32,
-    next_body_id: u32,
+    body_ids: IdAllocator,
 
     #[get = "pub"]
     tree: Tree,
     event_queue: BTreeSet<Event<H>>,
 
     handler: H,
+
+    /// The record of executed events, if enabled
+    #[get = "pub"]
+    journal: Option<Journal>,
+    history: Option<History<H>>,
 }
 
-fn next_id(id: &mut u32) -> u32 {
+pub(super) fn next_id(id: &mut u32) -> u32 {
     let ret = *id;
     *id += 1;
     ret
@@ -27,17 +35,17 @@ fn next_id(id: &mut u32) -> u32 {
 impl<H: Handler> System<H> {
     pub fn from_schema(schema: LargeBodySchema, handler: H) -> Self {
         fn to_body(
-            body_count: &mut u32,
+            body_ids: &mut IdAllocator,
             parent_index: &mut HashMap<BodyId, LargeBodyId>,
             schema: &LargeBodySchema,
             parent_mass: Option<Mass>,
         ) -> LargeBody {
-            let id = LargeBodyId(BodyId(next_id(body_count)));
+            let id = LargeBodyId(body_ids.alloc());
             let children = schema
                 .children()
                 .iter()
-                .map(|schema| {
-                    let body = to_body(body_count, parent_index, schema, Some(schema.mass()));
+                .map(|child| {
+                    let body = to_body(body_ids, parent_index, child, Some(schema.mass()));
                     (body.id(), body)
                 })
                 .collect::<HashMap<_, _>>();
@@ -51,7 +59,7 @@ impl<H: Handler> System<H> {
                         .eci()
                         .as_ref()
                         .expect("All child bodies must have an ECI");
-                    Some(Orbit::from_mpv(pm, eci.clone()))
+                    Some(Orbit::from_mpv(pm, eci.clone(), Time(0)))
                 }
                 None => None,
             };
@@ -64,23 +72,27 @@ impl<H: Handler> System<H> {
                 grav_radius: schema.grav_radius(),
                 mass: schema.mass(),
                 orbit,
+                rotation: schema.rotation(),
             }
         }
 
-        let mut body_count = 0u32;
+        let mut body_ids = IdAllocator::default();
         let mut parent_index = HashMap::new();
-        let mut root = to_body(&mut body_count, &mut parent_index, &schema, None);
+        let mut root = to_body(&mut body_ids, &mut parent_index, &schema, None);
 
         root.grav_radius = Length(f32::INFINITY);
 
         let tree = Tree::new(root, parent_index);
 
         System {
+            now: Time(0),
             next_event_id: 0,
-            next_body_id: body_count,
+            body_ids,
             tree,
             event_queue: BTreeSet::new(),
             handler,
+            journal: None,
+            history: None,
         }
     }
 
@@ -89,51 +101,716 @@ impl<H: Handler> System<H> {
     }
 
     pub(super) fn next_body_id(&mut self) -> BodyId {
-        BodyId(next_id(&mut self.next_body_id))
+        self.body_ids.alloc()
+    }
+
+    /// Removes a body and, for large bodies, all its descendants from the tree.
+    ///
+    /// Returns `None` if the body has already been removed.
+    /// The IDs of the removed bodies may be reused, but with a different generation,
+    /// so the old IDs are never resolved to the new bodies.
+    /// Events involving the removed bodies are also removed from the queue.
+    pub fn remove_body(&mut self, id: BodyId) -> Option<Body> {
+        let body = self.tree.remove_body(id)?;
+
+        fn collect_large(ids: &mut Vec<BodyId>, body: &LargeBody) {
+            ids.push(body.id().0);
+            for child in body.large.values() {
+                collect_large(ids, child);
+            }
+            ids.extend(body.small.keys().map(|&child| child.0));
+        }
+        let mut removed = Vec::new();
+        match &body {
+            Body::Large(body) => collect_large(&mut removed, body),
+            Body::Small(body) => removed.push(body.id().0),
+        }
+
+        // the freed indices are reused in reverse order, which must not depend on hash order
+        removed.sort();
+        for &id in &removed {
+            self.body_ids.free(id);
+        }
+        self.event_queue
+            .retain(|event| !removed.iter().any(|&id| event.involves(id)));
+
+        Some(body)
+    }
+
+    /// The pending events in the order they are executed
+    pub(super) fn events(&self) -> impl Iterator<Item = &Event<H>> {
+        self.event_queue.iter()
     }
 
     pub fn next_event(&self) -> Option<Time> {
         self.event_queue.iter().next().map(|event| event.time())
     }
 
+    /// Executes the events up to `t`.
+    ///
+    /// After seeking back, the commands issued originally up to `t` are replayed on the way,
+    /// so the history stays consistent with the snapshots.
     pub fn advance_event(&mut self, t: Time) {
-        loop {
-            let event = self.event_queue.iter().next();
-            let event = match event {
-                Some(event) => event,
-                None => return,
-            };
+        while let Some((time, commands)) = self.next_input(t) {
+            self.advance_to(time);
+            self.reserve(&commands);
+            self.apply_commands(commands, time);
+        }
+        self.advance_to(t);
+    }
+
+    /// Executes the events up to `t` without replaying inputs.
+    fn advance_to(&mut self, t: Time) {
+        while let Some(event) = self.event_queue.iter().next() {
             if event.time() > t {
-                return;
+                break;
             }
 
-            let key = (event.id(), event.time());
+            let key = (event.time(), event.id());
+            self.snapshot_if_due();
             let event = self
                 .event_queue
                 .take(&key)
                 .expect("Event was obtained in quue");
-            self.exec_event(t, event);
+            self.exec_event(event);
+        }
+        self.now = self.now.max(t);
+        self.snapshot_if_due();
+    }
+
+    /// Takes the next input in the history that was issued at or before `t` but not replayed.
+    fn next_input(&mut self, t: Time) -> Option<(Time, Vec<Command>)> {
+        let history = self.history.as_mut()?;
+        let (time, commands) = history.inputs.get(history.cursor)?;
+        if *time > t {
+            return None;
         }
+        history.cursor += 1;
+        Some((*time, commands.clone()))
     }
 
-    fn exec_event(&mut self, t: Time, event: Event<H>) {
+    fn exec_event(&mut self, event: Event<H>) {
+        let (t, id) = (event.time(), event.id());
+        self.now = self.now.max(t);
         match event.into_type() {
-            EventType::Collision(event) => self.on_collision(t, event),
-            EventType::FieldChange(event) => self.on_field_change(t, event),
-            EventType::Misc(f) => f(self),
+            EventType::Collision(event) => self.on_collision(t, id, event),
+            EventType::FieldChange(event) => self.on_field_change(t, id, event),
+            EventType::Timer(tag) => self.on_timer(t, id, tag),
+            EventType::Misc(f) => {
+                f(self);
+                self.record(t, id, JournalEvent::Misc, Vec::new());
+            }
         }
     }
 
-    fn on_collision(&mut self, t: Time, collision: Collision) {
-        let body1 = self.tree.get_body(collision.body1());
-        let body2 = self.tree.get_body(collision.body2());
+    /// Starts recording the executed events.
+    ///
+    /// Does nothing if the journal is already enabled.
+    pub fn enable_journal(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(Journal::default());
+        }
+    }
 
-        let (r1, r2) = self.handler.on_collision(body1, body2);
-        unimplemented!()
+    /// Stops recording the executed events and returns the journal.
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.journal.take()
     }
 
-    fn on_field_change(&mut self, t: Time, fc: FieldChange) {
-        let body = self.tree.get_body(fc.body());
+    /// Starts keeping snapshots at least `interval` apart, allowing `seek` to earlier times.
+    ///
+    /// Only the commands issued through `issue` are replayed by `seek`.
+    pub fn enable_history(&mut self, interval: i32)
+    where
+        H: Clone,
+    {
+        assert!(interval > 0, "Snapshot interval must be positive");
+        self.history = Some(History {
+            interval,
+            snapshots: Vec::new(),
+            inputs: Vec::new(),
+            cursor: 0,
+            clone_handler: H::clone,
+        });
+        self.snapshot_if_due();
+    }
+
+    fn snapshot_if_due(&mut self) {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return,
+        };
+        if let Some(last) = history.snapshots.last() {
+            if self.now.0 < last.now.0 + history.interval {
+                return;
+            }
+        }
+
+        let snapshot = Snapshot {
+            now: self.now,
+            next_event_id: self.next_event_id,
+            body_ids: self.body_ids.clone(),
+            tree: self.tree.clone(),
+            event_queue: self.event_queue.clone(),
+            handler: (history.clone_handler)(&self.handler),
+            inputs_len: history.cursor,
+            journal_len: self
+                .journal
+                .as_ref()
+                .map_or(0, |journal| journal.entries().len()),
+        };
+        history.snapshots.push(snapshot);
+    }
+
+    /// Issues commands at the current time.
+    ///
+    /// Unlike the other mutating methods, the commands are recorded in the history
+    /// and replayed by `seek`.
+    /// Issuing commands after seeking back discards the history after the current time.
+    pub fn issue<R>(&mut self, f: impl FnOnce(&Tree, &mut Commands<'_>) -> R) -> R {
+        let mut commands = Commands::new(self.now, &mut self.body_ids, &mut self.next_event_id);
+        let ret = f(&self.tree, &mut commands);
+        let commands = commands.into_commands();
+
+        if let Some(history) = &mut self.history {
+            history.truncate(self.now);
+            history.inputs.push((self.now, commands.clone()));
+            history.cursor += 1;
+        }
+        self.apply_commands(commands, self.now);
+        ret
+    }
+
+    /// Restores the state at `t` from the nearest snapshot and replays the commands issued since.
+    ///
+    /// The result is identical to the original run up to `t`.
+    /// Seeking forward after seeking back replays the commands that were issued originally.
+    pub fn seek(&mut self, t: Time) {
+        let history = self.history.as_mut().expect("History is not enabled");
+        let snapshot = history
+            .snapshot_before(t)
+            .expect("Cannot seek before the history was enabled");
+
+        self.now = snapshot.now;
+        self.next_event_id = snapshot.next_event_id;
+        self.body_ids = snapshot.body_ids.clone();
+        self.tree = snapshot.tree.clone();
+        self.event_queue = snapshot.event_queue.clone();
+        self.handler = (history.clone_handler)(&snapshot.handler);
+        if let Some(journal) = &mut self.journal {
+            journal.truncate(snapshot.journal_len);
+        }
+        history.cursor = snapshot.inputs_len;
+        self.advance_event(t);
+    }
+
+    /// Reserves the IDs used by replayed commands in the same order as when they were issued.
+    fn reserve(&mut self, commands: &[Command]) {
+        for command in commands {
+            match command {
+                Command::Spawn { id, .. } => {
+                    let reserved = self.body_ids.alloc();
+                    debug_assert_eq!(reserved, id.0, "Replay out of sync");
+                }
+                Command::Schedule { event, .. } => {
+                    let reserved = next_id(&mut self.next_event_id);
+                    debug_assert_eq!(reserved, event.0, "Replay out of sync");
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn record(&mut self, t: Time, id: EventId, kind: JournalEvent, commands: Vec<Command>) {
+        if let Some(journal) = &mut self.journal {
+            journal.push(JournalEntry::new(&self.tree, t, id, kind, commands));
+        }
+    }
+
+    fn on_timer(&mut self, t: Time, id: EventId, tag: u64) {
+        let mut commands = Commands::new(t, &mut self.body_ids, &mut self.next_event_id);
+        self.handler.on_timer(&self.tree, tag, &mut commands);
+        let commands = commands.into_commands();
+        let journal = self.journal.as_ref().map(|_| commands.clone());
+        self.apply_commands(commands, t);
+
+        if let Some(commands) = journal {
+            self.record(t, id, JournalEvent::Timer { tag }, commands);
+        }
+    }
+
+    /// Applies the commands issued by a handler callback in order.
+    ///
+    /// Commands referring to bodies that no longer exist and burns of the root body are ignored.
+    fn apply_commands(&mut self, commands: Vec<Command>, t: Time) {
+        for command in commands {
+            match command {
+                Command::Spawn {
+                    id,
+                    parent,
+                    mass,
+                    radius,
+                    eci,
+                } => {
+                    if self.tree.contains(parent.into()) {
+                        self.spawn_small_with_id(id, parent, mass, radius, eci, t);
+                    } else {
+                        self.body_ids.free(id.into());
+                    }
+                }
+                Command::Despawn { body } => {
+                    self.remove_body(body);
+                }
+                Command::Burn { body, delta_v } => {
+                    // burns of removed bodies and the root body are ignored
+                    let _ = self.burn(body, delta_v, t);
+                }
+                Command::Schedule { event, time, tag } => {
+                    self.schedule(Event::new(event, time, EventType::Timer(tag)));
+                }
+                Command::Cancel { event } => {
+                    self.cancel(event);
+                }
+            }
+        }
+    }
+
+    fn on_collision(&mut self, t: Time, id: EventId, collision: Collision) {
+        // either body may have been removed since the collision was scheduled
+        let (body1, body2) = match (
+            self.tree.try_get_body(collision.body1()),
+            self.tree.try_get_body(collision.body2()),
+        ) {
+            (Some(body1), Some(body2)) => (body1, body2),
+            _ => {
+                let bodies = vec![collision.body1(), collision.body2()];
+                self.record(t, id, JournalEvent::Skipped { bodies }, Vec::new());
+                return;
+            }
+        };
+
+        let mut commands = Commands::new(t, &mut self.body_ids, &mut self.next_event_id);
+        let resolution = self.handler.on_collision(body1, body2, &mut commands);
+        let mut commands = commands.into_commands();
+        let resolved = resolution.clone();
+
+        if let Some((r1, r2)) = resolved {
+            // the debris is spawned before the commands of the handler are applied
+            let mut spawns = self.resolve_collisions(t, &collision, r1, r2);
+            spawns.append(&mut commands);
+            commands = spawns;
+        }
+        let journal = self
+            .journal
+            .as_ref()
+            .map(|_| (resolution, commands.clone()));
+        self.apply_commands(commands, t);
+
+        if let Some((resolution, commands)) = journal {
+            let kind = JournalEvent::Collision {
+                body1: collision.body1(),
+                body2: collision.body2(),
+                resolution,
+            };
+            self.record(t, id, kind, commands);
+        }
+    }
+
+    /// Resolves a collision and returns the commands that spawn its debris.
+    fn resolve_collisions(
+        &mut self,
+        t: Time,
+        collision: &Collision,
+        r1: CollisionResolution,
+        r2: CollisionResolution,
+    ) -> Vec<Command> {
+        let (r1, mut debris) = r1.into_parts();
+        let (r2, debris2) = r2.into_parts();
+        debris.extend(debris2);
+        let debris = self.debris_states(collision.body1(), collision.body2(), &debris, t);
+
+        match (r1, r2) {
+            (CollisionResolution::Merge { radius }, _) => {
+                self.merge(collision.body1(), collision.body2(), radius, t)
+            }
+            (_, CollisionResolution::Merge { radius }) => {
+                self.merge(collision.body2(), collision.body1(), radius, t)
+            }
+            (r1, r2) => {
+                self.resolve_collision(t, collision.body1(), collision.body2(), r1);
+                self.resolve_collision(t, collision.body2(), collision.body1(), r2);
+            }
+        }
+
+        let mut spawns = Vec::new();
+        for (frames, debris, eci) in debris {
+            // the frame may have been removed by the resolutions
+            let parent = frames
+                .into_iter()
+                .find(|&frame| self.tree.contains(frame.into()))
+                .expect("The root body cannot be removed");
+            let parent_eci = self
+                .tree
+                .absolute_eci(parent.into(), t)
+                .expect("Frame exists");
+            spawns.push(Command::Spawn {
+                id: SmallBodyId(self.next_body_id()),
+                parent,
+                mass: debris.mass,
+                radius: debris.radius,
+                eci: eci - parent_eci,
+            });
+        }
+        spawns
+    }
+
+    /// Computes the absolute ECI of each debris from the state of the colliding bodies.
+    ///
+    /// The debris should be spawned in the closest common ancestor of the colliding bodies,
+    /// or the closest ancestor of it that still exists after the collision is resolved,
+    /// so each debris is returned with the list of candidate parents in order.
+    fn debris_states(
+        &self,
+        body1: BodyId,
+        body2: BodyId,
+        debris: &[Debris],
+        t: Time,
+    ) -> Vec<(Vec<LargeBodyId>, Debris, Eci)> {
+        if debris.is_empty() {
+            return Vec::new();
+        }
+
+        let (path, _) = self
+            .tree
+            .paths_to_common_ancestor(body1, body2)
+            .expect("Colliding bodies exist");
+        let frame = match path.last() {
+            Some(&last) => self.tree.parent(last).expect("Common ancestor is a parent"),
+            None => LargeBodyId(body1),
+        };
+        let mut frames = vec![frame];
+        frames.extend(
+            self.tree
+                .ancestors(frame.into())
+                .map(|(body, _)| LargeBodyId(body.id())),
+        );
+
+        let (body1, body2) = (self.tree.get_body(body1), self.tree.get_body(body2));
+        let eci1 = self.tree.absolute_eci(body1.id(), t).expect("Body exists");
+        let eci2 = self.tree.absolute_eci(body2.id(), t).expect("Body exists");
+        let (m1, m2) = (body1.mass().0, body2.mass().0);
+        let (r1, r2) = (body1.radius().0, body2.radius().0);
+
+        let impact = eci1.position() + (eci2.position() - eci1.position()) * (r1 / (r1 + r2));
+        let velocity = (eci1.velocity() * m1 + eci2.velocity() * m2) / (m1 + m2);
+
+        debris
+            .iter()
+            .map(|debris| {
+                let eci = Eci::new(impact + debris.offset, velocity + debris.velocity);
+                (frames.clone(), debris.clone(), eci)
+            })
+            .collect()
+    }
+
+    /// Adds a new small body orbiting `parent` with the given ECI relative to `parent`.
+    pub fn spawn_small(
+        &mut self,
+        parent: LargeBodyId,
+        mass: Mass,
+        radius: Length,
+        eci: Eci,
+        t: Time,
+    ) -> SmallBodyId {
+        let id = SmallBodyId(self.next_body_id());
+        self.spawn_small_with_id(id, parent, mass, radius, eci, t);
+        id
+    }
+
+    fn spawn_small_with_id(
+        &mut self,
+        id: SmallBodyId,
+        parent: LargeBodyId,
+        mass: Mass,
+        radius: Length,
+        eci: Eci,
+        t: Time,
+    ) {
+        let parent_mass = self.tree.get_large_body(parent).mass();
+        let body = SmallBody {
+            id,
+            mass,
+            radius,
+            motion: Motion::Orbiting(Orbit::from_mpv(parent_mass, eci, t)),
+        };
+        self.tree.insert_small(parent, body);
+    }
+
+    /// Changes the velocity of a body by `delta_v`.
+    ///
+    /// Landed bodies are launched with `delta_v` relative to the surface.
+    pub fn burn(&mut self, id: BodyId, delta_v: Vector, t: Time) -> Result<(), MotionError> {
+        if !self.tree.contains(id) {
+            return Err(MotionError::Removed);
+        }
+        let parent = self.tree.parent(id).ok_or(MotionError::Root)?;
+        if let BodyRef::Small(body) = self.tree.get_body(id) {
+            if let Motion::Landed(_) = body.motion() {
+                return self.launch(body.id(), delta_v, t);
+            }
+        }
+
+        let parent_mass = self.tree.get_large_body(parent).mass();
+        let eci = self.tree.eci_in_parent(id, t).expect("Body exists");
+        let eci = Eci::new(eci.position(), eci.velocity() + delta_v);
+        self.tree
+            .get_body_mut(id)
+            .set_orbit(Orbit::from_mpv(parent_mass, eci, t));
+        Ok(())
+    }
+
+    /// Resolves the collision of `id` with `other`.
+    fn resolve_collision(
+        &mut self,
+        t: Time,
+        id: BodyId,
+        other: BodyId,
+        resolution: CollisionResolution,
+    ) {
+        // the body is gone if it is a descendant of a large body removed by the other resolution,
+        // or if it has been merged into the other body
+        if !self.tree.contains(id) {
+            return;
+        }
+
+        match resolution {
+            CollisionResolution::Remove => {
+                self.remove_body(id);
+            }
+            CollisionResolution::Mutate(mutation) => self.mutate_body(t, id, mutation),
+            CollisionResolution::Land => {
+                // large bodies cannot land, and small bodies only land on the parent they hit
+                let hit_parent = self.tree.parent(id).map(BodyId::from) == Some(other);
+                if let BodyRef::Small(body) = self.tree.get_body(id) {
+                    if hit_parent {
+                        let id = body.id();
+                        self.land(id, t).expect("Body exists");
+                    }
+                }
+            }
+            CollisionResolution::Merge { .. } | CollisionResolution::Fragment { .. } => {
+                unreachable!("Merge and Fragment are handled in on_collision")
+            }
+        }
+    }
+
+    /// Merges `absorbed` into `survivor`, so that only `survivor` remains with the total mass.
+    ///
+    /// The survivor moves to the center of mass of the two bodies with a momentum-conserving
+    /// velocity, and its radius (the surface radius for large bodies) is set to `radius`.
+    /// If `absorbed` is a large body, its children are moved to its parent.
+    ///
+    /// The root body can never be absorbed, so it survives instead if passed as `absorbed`.
+    /// The root body stays in place when it survives, since it is the frame of the tree.
+    pub fn merge(&mut self, survivor: BodyId, absorbed: BodyId, radius: Length, t: Time) {
+        let (survivor, absorbed) = if absorbed == self.tree.root().id().into() {
+            (absorbed, survivor)
+        } else {
+            (survivor, absorbed)
+        };
+        let survivor_eci = self
+            .tree
+            .absolute_eci(survivor, t)
+            .expect("Survivor exists");
+        let absorbed_eci = self
+            .tree
+            .absolute_eci(absorbed, t)
+            .expect("Absorbed body exists");
+        let survivor_mass = self.tree.get_body(survivor).mass();
+        let absorbed_mass = self.tree.get_body(absorbed).mass();
+        let mass = Mass(survivor_mass.0 + absorbed_mass.0);
+
+        let weighted = |a: Vector, b: Vector| (a * survivor_mass.0 + b * absorbed_mass.0) / mass.0;
+        let merged_eci = if survivor == self.tree.root().id().into() {
+            survivor_eci.clone()
+        } else {
+            Eci::new(
+                weighted(survivor_eci.position(), absorbed_eci.position()),
+                weighted(survivor_eci.velocity(), absorbed_eci.velocity()),
+            )
+        };
+
+        if let BodyRef::Large(body) = self.tree.get_body(absorbed) {
+            let parent = self
+                .tree
+                .parent(absorbed)
+                .expect("The root body is never absorbed");
+            let children = body
+                .children()
+                .iter()
+                .map(|child| child.id())
+                .collect::<Vec<_>>();
+            for child in children {
+                self.reparent(child, parent, t);
+            }
+        }
+        self.remove_body(absorbed);
+
+        if let Some(parent) = self.tree.parent(survivor) {
+            let parent_eci = self
+                .tree
+                .absolute_eci(parent.into(), t)
+                .expect("Parent exists");
+            let parent_mass = self.tree.get_large_body(parent).mass();
+            let orbit = Orbit::from_mpv(parent_mass, merged_eci.clone() - parent_eci, t);
+            self.tree.get_body_mut(survivor).set_orbit(orbit);
+        }
+
+        match self.tree.get_body_mut(survivor) {
+            BodyMut::Large(body) => {
+                body.mass = mass;
+                body.surface_radius = radius;
+
+                // keep the children in place while the frame moves to the center of mass
+                let offset = survivor_eci - merged_eci;
+                for child in body.large.values_mut() {
+                    let orbit = child.orbit().as_ref().expect("Child bodies have an orbit");
+                    let eci = orbit.eci(t) + offset.clone();
+                    child.set_orbit(Some(Orbit::from_mpv(mass, eci, t)));
+                }
+                for child in body.small.values_mut() {
+                    if let Some(orbit) = child.orbit() {
+                        let eci = orbit.eci(t) + offset.clone();
+                        child.set_orbit(Orbit::from_mpv(mass, eci, t));
+                    }
+                }
+            }
+            BodyMut::Small(body) => {
+                body.mass = mass;
+                body.radius = radius;
+            }
+        }
+    }
+
+    /// Moves a body to another parent without changing its absolute position and velocity.
+    fn reparent(&mut self, id: BodyId, parent: LargeBodyId, t: Time) {
+        let eci = self
+            .tree
+            .relative_eci(id, parent.into(), t)
+            .expect("Body and parent exist");
+        let parent_mass = self.tree.get_large_body(parent).mass();
+        self.tree.move_body(id, parent);
+
+        let orbit = Orbit::from_mpv(parent_mass, eci, t);
+        self.tree.get_body_mut(id).set_orbit(orbit);
+    }
+
+    /// Rests a small body on the surface of its parent, below its current position.
+    ///
+    /// The body moves with the surface of its parent from then on,
+    /// so its pending field changes are cancelled.
+    pub fn land(&mut self, id: SmallBodyId, t: Time) -> Result<(), MotionError> {
+        let parent = self.tree.parent(id.into()).ok_or(MotionError::Removed)?;
+        let position = self
+            .tree
+            .eci_in_parent(id.into(), t)
+            .expect("Body exists")
+            .position();
+        let longitude = self.tree.get_large_body(parent).longitude(position, t);
+
+        let body = self.tree.get_body_mut(id.into()).borrow_small();
+        body.motion = Motion::Landed(Landing { longitude });
+        self.event_queue
+            .retain(|event| !event.changes_field_of(id.into()));
+        Ok(())
+    }
+
+    /// Launches a landed small body into orbit with `velocity` relative to the surface.
+    ///
+    /// The velocity of the surface due to the rotation of the parent is added to `velocity`.
+    /// Bodies that are not landed are left unchanged.
+    pub fn launch(
+        &mut self,
+        id: SmallBodyId,
+        velocity: Vector,
+        t: Time,
+    ) -> Result<(), MotionError> {
+        let parent = self.tree.parent(id.into()).ok_or(MotionError::Removed)?;
+        let parent_mass = self.tree.get_large_body(parent).mass();
+        let surface_eci = self.tree.eci_in_parent(id.into(), t).expect("Body exists");
+
+        let body = self.tree.get_body_mut(id.into()).borrow_small();
+        if let Motion::Orbiting(_) = body.motion() {
+            return Err(MotionError::NotLanded);
+        }
+        let eci = Eci::new(surface_eci.position(), surface_eci.velocity() + velocity);
+        body.set_orbit(Orbit::from_mpv(parent_mass, eci, t));
+        Ok(())
+    }
+
+    fn mutate_body(&mut self, t: Time, id: BodyId, mutation: BodyMutation) {
+        let parent_mass = self
+            .tree
+            .parent(id)
+            .map(|parent| self.tree.get_large_body(parent).mass());
+        let position = self
+            .tree
+            .eci_in_parent(id, t)
+            .expect("Body exists")
+            .position();
+
+        match self.tree.get_body_mut(id) {
+            BodyMut::Large(body) => {
+                if let Some(radius) = mutation.surface_radius {
+                    body.surface_radius = radius;
+                }
+                if let Some(radius) = mutation.grav_radius {
+                    body.grav_radius = radius;
+                }
+                // the root body cannot move, like in `burn`
+                if let (Some(velocity), Some(parent_mass)) = (mutation.velocity, parent_mass) {
+                    let eci = Eci::new(position, velocity);
+                    body.set_orbit(Some(Orbit::from_mpv(parent_mass, eci, t)));
+                }
+                if let Some(mass) = mutation.mass {
+                    body.mass = mass;
+                    // the orbits of the children depend on the mass of this body
+                    for child in body.large.values_mut() {
+                        let orbit = child.orbit().as_ref().expect("Child bodies have an orbit");
+                        let eci = orbit.eci(t);
+                        child.set_orbit(Some(Orbit::from_mpv(mass, eci, t)));
+                    }
+                    for child in body.small.values_mut() {
+                        if let Some(orbit) = child.orbit() {
+                            let eci = orbit.eci(t);
+                            child.set_orbit(Orbit::from_mpv(mass, eci, t));
+                        }
+                    }
+                }
+            }
+            BodyMut::Small(body) => {
+                if let Some(radius) = mutation.surface_radius {
+                    body.radius = radius;
+                }
+                if let Some(mass) = mutation.mass {
+                    body.mass = mass;
+                }
+                if let Some(velocity) = mutation.velocity {
+                    // this also launches landed bodies
+                    let eci = Eci::new(position, velocity);
+                    let parent_mass = parent_mass.expect("Small bodies have a parent");
+                    body.set_orbit(Orbit::from_mpv(parent_mass, eci, t));
+                }
+            }
+        }
+    }
+
+    fn on_field_change(&mut self, t: Time, id: EventId, fc: FieldChange) {
+        let bodies = vec![fc.body(), fc.from().into(), fc.to().into()];
+        if !bodies.iter().all(|&body| self.tree.contains(body)) {
+            self.record(t, id, JournalEvent::Skipped { bodies }, Vec::new());
+            return;
+        }
         let from = self.tree.get_large_body(fc.from());
         let to = self.tree.get_large_body(fc.to());
         let to_mass = to.mass();
@@ -152,79 +829,98 @@ impl<H: Handler> System<H> {
             panic!("Field of change must be between parent and child")
         };
 
-        drop(from);
-        drop(to);
-
-        match body {
-            BodyRef::Large(body) => {
-                let id = body.id();
-                let from_eci = body
-                    .orbit()
-                    .as_ref()
-                    .expect("Body in FieldChange must be a child")
-                    .eci(t);
-                let mut body = self
-                    .tree
-                    .get_large_body_mut(fc.from())
-                    .large
-                    .remove(&id)
-                    .expect("Event data out of sync");
-
-                let to_eci = match dir {
-                    Direction::ParentToChild => self
-                        .tree
-                        .get_large_body(fc.to())
-                        .from_eci_in_parent(t, &from_eci),
-                    Direction::ChildToParent => self
-                        .tree
-                        .get_large_body(fc.from())
-                        .to_eci_in_parent(t, &from_eci),
-                };
-                body.set_orbit(Some(Orbit::from_mpv(to_mass, to_eci)));
-
-                self.tree.get_large_body_mut(fc.to()).large.insert(id, body);
+        let from_eci = match self.tree.get_body(fc.body()) {
+            BodyRef::Large(body) => body
+                .orbit()
+                .as_ref()
+                .expect("Body in FieldChange must be a child")
+                .eci(t),
+            BodyRef::Small(body) => body
+                .orbit()
+                .expect("Landed bodies cannot change fields")
+                .eci(t),
+        };
+        let to_eci = match dir {
+            Direction::ParentToChild => to.from_eci_in_parent(t, &from_eci),
+            Direction::ChildToParent => from.to_eci_in_parent(t, &from_eci),
+        };
+
+        self.tree.move_body(fc.body(), fc.to());
+        self.tree
+            .get_body_mut(fc.body())
+            .set_orbit(Orbit::from_mpv(to_mass, to_eci, t));
+
+        let body = self.tree.get_body(fc.body());
+        let from = self.tree.get_large_body(fc.from());
+        let to = self.tree.get_large_body(fc.to());
+        let mut commands = Commands::new(t, &mut self.body_ids, &mut self.next_event_id);
+        match dir {
+            Direction::ParentToChild => {
+                self.handler
+                    .on_enter_subfield(body, from, to, &mut commands)
             }
-            BodyRef::Small(body) => {
-                let id = body.id();
-                let from_eci = body.orbit().eci(t);
-                let mut body = self
-                    .tree
-                    .get_large_body_mut(fc.from())
-                    .small
-                    .remove(&id)
-                    .expect("Event data out of sync");
-
-                let to_eci = match dir {
-                    Direction::ParentToChild => self
-                        .tree
-                        .get_large_body(fc.to())
-                        .from_eci_in_parent(t, &from_eci),
-                    Direction::ChildToParent => self
-                        .tree
-                        .get_large_body(fc.from())
-                        .to_eci_in_parent(t, &from_eci),
-                };
-                body.set_orbit(Orbit::from_mpv(to_mass, to_eci));
-
-                self.tree.get_large_body_mut(fc.to()).small.insert(id, body);
+            Direction::ChildToParent => {
+                self.handler.on_exit_subfield(body, from, to, &mut commands)
             }
         }
+        let commands = commands.into_commands();
+        let journal = self.journal.as_ref().map(|_| commands.clone());
+        self.apply_commands(commands, t);
 
-        unimplemented!()
+        if let Some(commands) = journal {
+            let kind = JournalEvent::FieldChange {
+                body: fc.body(),
+                from: fc.from(),
+                to: fc.to(),
+            };
+            self.record(t, id, kind, commands);
+        }
     }
 
     pub fn schedule(&mut self, event: Event<H>) {
         self.event_queue.insert(event);
     }
+
+    /// Schedules a call to `Handler::on_timer` with `tag` at `time`.
+    pub fn schedule_timer(&mut self, time: Time, tag: u64) -> EventId {
+        let id = self.next_event_id();
+        self.schedule(Event::new(id, time, EventType::Timer(tag)));
+        id
+    }
+
+    /// Removes an event from the queue.
+    ///
+    /// Returns false if the event has already been executed or cancelled.
+    pub fn cancel(&mut self, id: EventId) -> bool {
+        let len = self.event_queue.len();
+        self.event_queue.retain(|event| event.id() != id);
+        self.event_queue.len() != len
+    }
 }
 
-#[derive(Debug, derive_new::new)]
+#[derive(Debug, Clone, derive_new::new)]
 pub struct Tree {
     root: LargeBody,
     parent_index: HashMap<BodyId, LargeBodyId>,
 }
 
 impl Tree {
+    pub fn root(&self) -> &LargeBody {
+        &self.root
+    }
+
+    /// Whether the body still exists in the tree.
+    ///
+    /// This is false if the body has been removed, even if its index has been reused.
+    pub fn contains(&self, id: BodyId) -> bool {
+        id == self.root.id().0 || self.parent_index.contains_key(&id)
+    }
+
+    /// Returns the parent of the body, or `None` for the root body and removed bodies.
+    pub fn parent(&self, id: BodyId) -> Option<LargeBodyId> {
+        self.parent_index.get(&id).copied()
+    }
+
     pub fn get_large_body(&self, id: LargeBodyId) -> &LargeBody {
         if id == self.root.id() {
             return &self.root;
@@ -232,7 +928,7 @@ impl Tree {
 
         // We allow panic here, because BodyId should not be possible to create without a
         // corresponding object.
-        // The ID may also be a dangling one, but then this implies we are dealing with a leak issue
+        // Use `try_get_large_body` for IDs that may refer to removed bodies.
         self.get_large_body(self.parent_index[id.as_ref()])
             .large
             .get(&id)
@@ -250,6 +946,23 @@ impl Tree {
             .unwrap()
     }
 
+    pub fn try_get_large_body(&self, id: LargeBodyId) -> Option<&LargeBody> {
+        if self.contains(id.0) {
+            Some(self.get_large_body(id))
+        } else {
+            None
+        }
+    }
+
+    /// Looks up a body, returning `None` if the body has been removed.
+    pub fn try_get_body<'t>(&'t self, id: BodyId) -> Option<BodyRef<'t>> {
+        if self.contains(id) {
+            Some(self.get_body(id))
+        } else {
+            None
+        }
+    }
+
     pub fn get_body<'t>(&'t self, id: BodyId) -> BodyRef<'t> {
         if id == self.root.id().0 {
             return BodyRef::Large(&self.root);
@@ -269,4 +982,636 @@ impl Tree {
             .get_child_mut(id)
             .unwrap()
     }
+
+    pub(super) fn insert_small(&mut self, parent: LargeBodyId, body: SmallBody) {
+        self.parent_index.insert(body.id().0, parent);
+        self.get_large_body_mut(parent)
+            .small
+            .insert(body.id(), body);
+    }
+
+    /// Moves a body, together with its descendants, to the children of `parent`.
+    ///
+    /// The orbit of the body is not updated.
+    pub(super) fn move_body(&mut self, id: BodyId, parent: LargeBodyId) {
+        let old_parent = self.parent_index[&id];
+        let old_parent = self.get_large_body_mut(old_parent);
+        if let Some(body) = old_parent.large.remove(&LargeBodyId(id)) {
+            self.get_large_body_mut(parent)
+                .large
+                .insert(LargeBodyId(id), body);
+        } else {
+            let body = old_parent
+                .small
+                .remove(&SmallBodyId(id))
+                .expect("parent_index out of sync");
+            self.get_large_body_mut(parent)
+                .small
+                .insert(SmallBodyId(id), body);
+        }
+        self.parent_index.insert(id, parent);
+    }
+
+    /// Detaches a body from the tree, together with all its descendants.
+    ///
+    /// The root body cannot be removed.
+    pub(super) fn remove_body(&mut self, id: BodyId) -> Option<Body> {
+        let parent = self.parent_index.remove(&id)?;
+        let parent = self.get_large_body_mut(parent);
+        let body = if let Some(body) = parent.large.remove(&LargeBodyId(id)) {
+            Body::Large(body)
+        } else {
+            let body = parent
+                .small
+                .remove(&SmallBodyId(id))
+                .expect("parent_index out of sync");
+            Body::Small(body)
+        };
+
+        fn unindex(parent_index: &mut HashMap<BodyId, LargeBodyId>, body: &LargeBody) {
+            for (&child_id, child) in &body.large {
+                parent_index.remove(&child_id.0);
+                unindex(parent_index, child);
+            }
+            for &child_id in body.small.keys() {
+                parent_index.remove(&child_id.0);
+            }
+        }
+        if let Body::Large(body) = &body {
+            unindex(&mut self.parent_index, body);
+        }
+
+        Some(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::math::Vector;
+
+    fn system() -> System<()> {
+        with_handler(())
+    }
+
+    fn with_handler<H: Handler>(handler: H) -> System<H> {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "mass": 1e6,
+            "children": [{
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+                "children": [{
+                    "surface_radius": 1.0,
+                    "grav_radius": 20.0,
+                    "mass": 1.0,
+                    "eci": {"position": [50.0, 0.0], "velocity": [0.0, 4.47]},
+                }],
+            }],
+        }))
+        .expect("Invalid schema");
+        System::from_schema(schema, handler)
+    }
+
+    fn planet<H: Handler>(system: &System<H>) -> LargeBodyId {
+        system.tree().root().children()[0].borrow_large().id()
+    }
+
+    fn spawn<H: Handler>(system: &mut System<H>, parent: LargeBodyId) -> SmallBodyId {
+        let eci = Eci::new(Vector::new(30.0, 0.0), Vector::new(0.0, 5.0));
+        system.spawn_small(parent, Mass(1.0), Length(1.0), eci, Time(0))
+    }
+
+    #[test]
+    fn test_from_schema_orbits_parent() {
+        // the schema velocities are circular around the parent mass, not the child mass
+        let system = system();
+        let planet = system.tree().root().children()[0].borrow_large();
+        let moon = planet.children()[0].borrow_large();
+        for &body in &[planet, moon] {
+            let orbit = body.orbit().as_ref().expect("Child bodies orbit");
+            assert!(orbit.eccentricity() < 1e-3, "{}", orbit.eccentricity());
+        }
+    }
+
+    #[test]
+    fn test_remove_bumps_generation() {
+        let mut system = system();
+        let root = system.tree().root().id();
+        let old = spawn(&mut system, root);
+        assert!(system.remove_body(old.into()).is_some());
+
+        let new = spawn(&mut system, root);
+        let (old, new) = (BodyId::from(old), BodyId::from(new));
+        assert_eq!(new.index(), old.index());
+        assert_eq!(new.generation(), old.generation() + 1);
+
+        assert!(!system.tree().contains(old));
+        assert!(system.tree().try_get_body(old).is_none());
+        assert!(system.tree().parent(old).is_none());
+        assert!(system.remove_body(old).is_none());
+        assert!(system.tree().try_get_body(new).is_some());
+    }
+
+    #[test]
+    fn test_remove_subtree_deterministic() {
+        let respawned = (0..20)
+            .map(|_| {
+                let mut system = system();
+                let planet = planet(&system);
+                for _ in 0..4 {
+                    spawn(&mut system, planet);
+                }
+                let descendants = system
+                    .tree()
+                    .descendants(planet)
+                    .map(|(body, _)| body.id())
+                    .collect::<Vec<_>>();
+                system.remove_body(planet.into());
+                for id in descendants {
+                    assert!(system.tree().try_get_body(id).is_none());
+                }
+
+                let root = system.tree().root().id();
+                (0..6)
+                    .map(|_| BodyId::from(spawn(&mut system, root)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert!(respawned.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    /// Lands every colliding body
+    struct Lander;
+
+    impl Handler for Lander {
+        fn on_collision(
+            &mut self,
+            _: BodyRef<'_>,
+            _: BodyRef<'_>,
+            _: &mut Commands<'_>,
+        ) -> Option<(CollisionResolution, CollisionResolution)> {
+            Some((CollisionResolution::Land, CollisionResolution::Land))
+        }
+    }
+
+    #[test]
+    fn test_land() {
+        let mut system = with_handler(Lander);
+        let planet = planet(&system);
+        let root = system.tree().root().id();
+        let body = spawn(&mut system, planet);
+
+        let id = system.next_event_id();
+        let fc = FieldChange {
+            body: body.into(),
+            from: planet,
+            to: root,
+        };
+        system.schedule(Event::new(id, Time(50), EventType::FieldChange(fc)));
+        let id = system.next_event_id();
+        let collision = Collision {
+            body1: body.into(),
+            body2: planet.into(),
+        };
+        system.schedule(Event::new(id, Time(10), EventType::Collision(collision)));
+        system.advance_event(Time(100));
+
+        // the planet cannot land and the pending field change of the body is cancelled
+        let tree = system.tree();
+        assert_eq!(tree.get_large_body(planet).surface_radius(), Length(10.0));
+        assert!(tree.get_large_body(planet).orbit().is_some());
+        assert!(tree.get_body(body.into()).orbit().is_none());
+        assert_eq!(tree.parent(body.into()), Some(planet));
+
+        let launch = Vector::new(0.0, 10.0);
+        assert_eq!(system.launch(body, launch, Time(100)), Ok(()));
+        assert!(system.tree().get_body(body.into()).orbit().is_some());
+        assert_eq!(
+            system.launch(body, launch, Time(100)),
+            Err(MotionError::NotLanded)
+        );
+
+        system.remove_body(body.into());
+        assert_eq!(
+            system.launch(body, launch, Time(100)),
+            Err(MotionError::Removed)
+        );
+        assert_eq!(system.land(body, Time(100)), Err(MotionError::Removed));
+    }
+
+    /// Schedules a collision between two bodies at `t`.
+    fn collide<H: Handler>(system: &mut System<H>, body1: BodyId, body2: BodyId, t: Time) {
+        let id = system.next_event_id();
+        let collision = Collision { body1, body2 };
+        system.schedule(Event::new(id, t, EventType::Collision(collision)));
+    }
+
+    #[test]
+    fn test_land_sibling() {
+        let mut system = with_handler(Lander);
+        let planet = planet(&system);
+        let body1 = spawn(&mut system, planet);
+        let body2 = spawn(&mut system, planet);
+        let orbits = |system: &System<Lander>| {
+            let orbit = |id: SmallBodyId| system.tree().get_body(id.into()).orbit().cloned();
+            (orbit(body1), orbit(body2))
+        };
+        let before = orbits(&system);
+        collide(&mut system, body1.into(), body2.into(), Time(10));
+        system.advance_event(Time(20));
+
+        // neither body hit its parent, so neither lands
+        let (orbit1, orbit2) = orbits(&system);
+        assert!(orbit1.is_some() && orbit2.is_some());
+        assert_eq!(format!("{:?}", (orbit1, orbit2)), format!("{:?}", before));
+    }
+
+    /// Logs the callbacks it receives and resolves collisions with `resolution`
+    struct Logger {
+        name: &'static str,
+        log: Rc<RefCell<Vec<&'static str>>>,
+        resolution: Option<CollisionResolution>,
+    }
+
+    impl Handler for Logger {
+        fn on_collision(
+            &mut self,
+            _: BodyRef<'_>,
+            _: BodyRef<'_>,
+            _: &mut Commands<'_>,
+        ) -> Option<(CollisionResolution, CollisionResolution)> {
+            self.log.borrow_mut().push(self.name);
+            let resolution = self.resolution.clone()?;
+            Some((resolution.clone(), resolution))
+        }
+
+        fn on_timer(&mut self, _: &Tree, _: u64, _: &mut Commands<'_>) {
+            self.log.borrow_mut().push(self.name);
+        }
+    }
+
+    #[test]
+    fn test_tuple_handler() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let logger = |name, resolution| Logger {
+            name,
+            log: Rc::clone(&log),
+            resolution,
+        };
+        let mutation = BodyMutation {
+            mass: Some(Mass(5.0)),
+            ..BodyMutation::default()
+        };
+        let handler = (
+            logger("a", None),
+            logger("b", Some(CollisionResolution::Remove)),
+            logger("c", Some(CollisionResolution::Mutate(mutation))),
+        );
+        let mut system = with_handler(handler);
+        let planet = planet(&system);
+        let (body1, body2) = (spawn(&mut system, planet), spawn(&mut system, planet));
+
+        system.enable_journal();
+        collide(&mut system, body1.into(), body2.into(), Time(10));
+        system.schedule_timer(Time(20), 0);
+        system.advance_event(Time(20));
+
+        // every handler sees every event in order, and the first resolution wins
+        assert_eq!(*log.borrow(), vec!["a", "b", "c", "a", "b", "c"]);
+        assert!(!system.tree().contains(body1.into()));
+        assert!(!system.tree().contains(body2.into()));
+        let journal = system.take_journal().unwrap();
+        match journal.entries()[0].kind() {
+            JournalEvent::Collision {
+                resolution: Some((CollisionResolution::Remove, CollisionResolution::Remove)),
+                ..
+            } => (),
+            kind => panic!("Unexpected event {:?}", kind),
+        }
+    }
+
+    /// Fragments the first colliding body, resolving it with `collider`
+    struct Fragmenter {
+        collider: CollisionResolution,
+        debris: Vec<Debris>,
+    }
+
+    impl Handler for Fragmenter {
+        fn on_collision(
+            &mut self,
+            _: BodyRef<'_>,
+            _: BodyRef<'_>,
+            _: &mut Commands<'_>,
+        ) -> Option<(CollisionResolution, CollisionResolution)> {
+            let fragment = CollisionResolution::Fragment {
+                collider: Box::new(self.collider.clone()),
+                debris: self.debris.clone(),
+            };
+            Some((
+                fragment,
+                CollisionResolution::Mutate(BodyMutation::default()),
+            ))
+        }
+    }
+
+    fn fragmenter(collider: CollisionResolution) -> System<Fragmenter> {
+        let debris = (1..=3)
+            .map(|i| Debris {
+                mass: Mass(0.1),
+                radius: Length(0.5),
+                offset: Vector::new(i as f32, 0.0),
+                velocity: Vector::new(0.0, i as f32),
+            })
+            .collect();
+        with_handler(Fragmenter { collider, debris })
+    }
+
+    #[test]
+    fn test_fragment() {
+        let mut system = fragmenter(CollisionResolution::Mutate(BodyMutation::default()));
+        let planet = planet(&system);
+        let moon = system.tree().get_large_body(planet).children()[0].id();
+        let body = BodyId::from(spawn(&mut system, planet));
+        let t = Time(10);
+
+        let tree = system.tree();
+        let eci = |id: BodyId| tree.absolute_eci(id, t).unwrap();
+        // both bodies have a radius and mass of 1
+        let impact = (eci(body).position() + eci(moon).position()) / 2.0;
+        let velocity = (eci(body).velocity() + eci(moon).velocity()) / 2.0;
+
+        system.enable_journal();
+        collide(&mut system, body, moon, t);
+        system.advance_event(t);
+
+        // the debris orbits the common parent of the colliders and is journaled as spawns
+        let journal = system.take_journal().unwrap();
+        let commands = journal.entries()[0].commands();
+        assert_eq!(commands.len(), 3);
+        let tree = system.tree();
+        assert_eq!(tree.small_bodies().count(), 4);
+        for (i, command) in commands.iter().enumerate() {
+            let id = match command {
+                Command::Spawn { id, parent, .. } => {
+                    assert_eq!(*parent, planet);
+                    BodyId::from(*id)
+                }
+                command => panic!("Unexpected command {:?}", command),
+            };
+            assert_eq!(tree.parent(id), Some(planet));
+            let offset = (i + 1) as f32;
+            let debris = tree.absolute_eci(id, t).unwrap();
+            assert_close(debris.position(), impact + Vector::new(offset, 0.0));
+            assert_close(debris.velocity(), velocity + Vector::new(0.0, offset));
+        }
+    }
+
+    #[test]
+    fn test_fragment_removed_frame() {
+        let mut system = fragmenter(CollisionResolution::Remove);
+        let root = system.tree().root().id();
+        let planet = planet(&system);
+        let moon = system.tree().get_large_body(planet).children()[0].id();
+        collide(&mut system, planet.into(), moon, Time(10));
+        system.advance_event(Time(10));
+
+        // the planet is removed with its moon, so the debris moves up to the root
+        let tree = system.tree();
+        assert!(!tree.contains(planet.into()) && !tree.contains(moon));
+        let parents = tree
+            .small_bodies()
+            .map(|(body, _)| tree.parent(body.id()))
+            .collect::<Vec<_>>();
+        assert_eq!(parents, vec![Some(root); 3]);
+    }
+
+    /// Sets the velocity of every colliding body
+    struct Accelerator;
+
+    impl Handler for Accelerator {
+        fn on_collision(
+            &mut self,
+            _: BodyRef<'_>,
+            _: BodyRef<'_>,
+            _: &mut Commands<'_>,
+        ) -> Option<(CollisionResolution, CollisionResolution)> {
+            let mutation = BodyMutation {
+                velocity: Some(Vector::new(0.0, 30.0)),
+                ..BodyMutation::default()
+            };
+            let resolution = CollisionResolution::Mutate(mutation);
+            Some((resolution.clone(), resolution))
+        }
+    }
+
+    #[test]
+    fn test_mutate_root_velocity() {
+        let mut system = with_handler(Accelerator);
+        let root = system.tree().root().id();
+        let planet = planet(&system);
+        collide(&mut system, root.into(), planet.into(), Time(10));
+        system.advance_event(Time(20));
+
+        // the root body cannot move, but the planet does
+        let tree = system.tree();
+        assert!(tree.root().orbit().is_none());
+        let velocity = tree
+            .eci_in_parent(planet.into(), Time(10))
+            .unwrap()
+            .velocity();
+        assert_close(velocity, Vector::new(0.0, 30.0));
+    }
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).norm() < 1e-2, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut system = system();
+        let root = system.tree().root().id();
+        let planet = planet(&system);
+        let body = spawn(&mut system, root);
+        let moon = system.tree().get_large_body(planet).children()[0].id();
+        let t = Time(100);
+
+        let tree = system.tree();
+        let eci = |id: BodyId| tree.absolute_eci(id, t).unwrap();
+        let (planet_mass, body_mass) = (tree.get_body(planet.into()).mass().0, 1.0);
+        let total = planet_mass + body_mass;
+        let center = (eci(planet.into()).position() * planet_mass
+            + eci(body.into()).position() * body_mass)
+            / total;
+        let momentum =
+            eci(planet.into()).velocity() * planet_mass + eci(body.into()).velocity() * body_mass;
+        let moon_eci = eci(moon);
+
+        // the small body survives and the moon of the absorbed planet is moved to the root
+        system.merge(body.into(), planet.into(), Length(12.0), t);
+        let tree = system.tree();
+        assert!(!tree.contains(planet.into()));
+        assert_eq!(tree.parent(moon), Some(root));
+        let moved = tree.absolute_eci(moon, t).unwrap();
+        assert_close(moved.position(), moon_eci.position());
+        assert_close(moved.velocity(), moon_eci.velocity());
+
+        let survivor = tree.get_body(body.into());
+        assert_eq!(survivor.mass(), Mass(total));
+        assert_eq!(survivor.radius(), Length(12.0));
+        let merged = tree.absolute_eci(body.into(), t).unwrap();
+        assert_close(merged.position(), center);
+        assert_close(merged.velocity() * total, momentum);
+    }
+
+    #[test]
+    fn test_merge_into_root() {
+        let mut system = system();
+        let root = system.tree().root().id();
+        let planet = planet(&system);
+        let moon = system.tree().get_large_body(planet).children()[0].id();
+        let t = Time(100);
+        let moon_eci = system.tree().absolute_eci(moon, t).unwrap();
+
+        // the root survives even if it is passed as the absorbed body
+        system.merge(planet.into(), root.into(), Length(200.0), t);
+        let tree = system.tree();
+        assert!(!tree.contains(planet.into()));
+        assert_eq!(tree.root().mass(), Mass(1e6 + 1000.0));
+        assert_eq!(tree.root().surface_radius(), Length(200.0));
+        assert_eq!(tree.parent(moon), Some(root));
+        let moved = tree.absolute_eci(moon, t).unwrap();
+        assert_close(moved.position(), moon_eci.position());
+        assert_close(moved.velocity(), moon_eci.velocity());
+    }
+
+    /// Issues invalid commands from a timer with tag 0
+    #[derive(Debug, Default)]
+    struct Rogue {
+        timers: Vec<(Time, u64)>,
+    }
+
+    impl Handler for Rogue {
+        fn on_timer(&mut self, tree: &Tree, tag: u64, commands: &mut Commands<'_>) {
+            self.timers.push((commands.now(), tag));
+            if tag == 0 {
+                commands.burn(tree.root().id().into(), Vector::new(1.0, 0.0));
+                commands.schedule(Time(0), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        let mut system = with_handler(Rogue::default());
+        let root = system.tree().root().id();
+        assert_eq!(
+            system.burn(root.into(), Vector::new(1.0, 0.0), Time(0)),
+            Err(MotionError::Root)
+        );
+
+        // the burn is ignored and the timer in the past is clamped to the current time
+        system.schedule_timer(Time(50), 0);
+        system.advance_event(Time(100));
+        assert_eq!(system.handler.timers, vec![(Time(50), 0), (Time(50), 1)]);
+    }
+
+    #[test]
+    fn test_journal_skipped() {
+        let mut system = system();
+        let root = system.tree().root().id();
+        let planet = planet(&system);
+        let body = BodyId::from(spawn(&mut system, root));
+        system.remove_body(body);
+        system.enable_journal();
+
+        let id = system.next_event_id();
+        let collision = Collision {
+            body1: body,
+            body2: planet.into(),
+        };
+        system.schedule(Event::new(id, Time(10), EventType::Collision(collision)));
+        let id = system.next_event_id();
+        let fc = FieldChange {
+            body,
+            from: root,
+            to: planet,
+        };
+        system.schedule(Event::new(id, Time(20), EventType::FieldChange(fc)));
+        system.advance_event(Time(100));
+
+        let journal = system.take_journal().unwrap();
+        let skipped = journal
+            .entries()
+            .iter()
+            .map(|entry| match entry.kind() {
+                JournalEvent::Skipped { bodies } => (entry.time(), bodies[0]),
+                kind => panic!("Unexpected event {:?}", kind),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(skipped, vec![(Time(10), body), (Time(20), body)]);
+    }
+
+    /// Runs a system with history, issuing a burn at 20 and a spawn at 60,
+    /// and returns the checksums at the given times.
+    fn run_with_history(times: &[i32]) -> (System<()>, Vec<(Time, u64)>) {
+        let mut system = system();
+        let root = system.tree().root().id();
+        let body = spawn(&mut system, root);
+        system.enable_history(10);
+
+        let mut checksums = Vec::new();
+        for &t in times {
+            if t > 20 && system.now < Time(20) {
+                system.advance_event(Time(20));
+                system.issue(|_, commands| commands.burn(body.into(), Vector::new(1.0, 0.0)));
+            }
+            if t > 60 && system.now < Time(60) {
+                system.advance_event(Time(60));
+                system.issue(|tree, commands| {
+                    let eci = Eci::new(Vector::new(500.0, 0.0), Vector::new(0.0, 40.0));
+                    commands.spawn(tree.root().id(), Mass(1.0), Length(1.0), eci)
+                });
+            }
+            system.advance_event(Time(t));
+            checksums.push((Time(t), system.checksum()));
+        }
+        (system, checksums)
+    }
+
+    #[test]
+    fn test_seek_then_advance() {
+        let (mut system, checksums) = run_with_history(&[40, 100]);
+        system.seek(Time(40));
+        assert_eq!(system.checksum(), checksums[0].1);
+
+        // the spawn at 60 is replayed by advancing without seeking
+        system.advance_event(Time(100));
+        assert_eq!(system.checksum(), checksums[1].1);
+        system.seek(Time(100));
+        assert_eq!(system.checksum(), checksums[1].1);
+    }
+
+    #[test]
+    fn test_seek_back_and_forth() {
+        let (mut system, checksums) = run_with_history(&[5, 15, 30, 45, 70, 85, 100]);
+        for &i in &[6, 2, 4, 0, 3, 5, 1, 6] {
+            let (t, checksum) = checksums[i];
+            system.seek(t);
+            assert_eq!(system.checksum(), checksum, "seek to {:?}", t);
+        }
+
+        // issuing after a seek discards the inputs after it
+        system.seek(Time(30));
+        system.issue(|_, _| ());
+        system.advance_event(Time(100));
+        assert_ne!(system.checksum(), checksums[6].1);
+        system.seek(Time(15));
+        assert_eq!(system.checksum(), checksums[1].1);
+    }
 }
@@ -0,0 +1,190 @@
+use std::f64::consts::PI;
+
+use getset::*;
+
+use super::*;
+use crate::math::{self, Length, Time, TransferCost, Vector};
+
+/// A plan for transferring from one large body to a sibling large body
+#[derive(Debug, Clone, Getters, CopyGetters, serde::Serialize, serde::Deserialize)]
+pub struct TransferPlan {
+    /// The angle from the origin to the target around the parent, in the direction of their
+    /// orbits, in the range [0, 2pi)
+    #[get_copy = "pub"]
+    phase_angle: f64,
+    /// The phase angle at which a Hohmann transfer should depart to meet the target on arrival
+    #[get_copy = "pub"]
+    departure_phase_angle: f64,
+    /// The next departure time for a Hohmann transfer,
+    /// or `None` if the phase angle never changes
+    #[get_copy = "pub"]
+    next_window: Option<Time>,
+    /// The time between consecutive departure windows
+    #[get_copy = "pub"]
+    synodic_period: Option<f64>,
+    #[get = "pub"]
+    hohmann: TransferCost,
+    /// The bi-elliptic transfer through the edge of the sphere of influence of the parent,
+    /// or `None` if the sphere of influence is unbounded
+    #[get = "pub"]
+    bi_elliptic: Option<TransferCost>,
+}
+
+impl Tree {
+    /// Plans a transfer at time `t` between two large bodies orbiting the same parent.
+    ///
+    /// The orbits are approximated as circular orbits with their semi-major axes as radii.
+    /// Returns `None` if the bodies are not siblings, either orbit is open,
+    /// or the bodies orbit in opposite directions.
+    pub fn plan_transfer(
+        &self,
+        origin: LargeBodyId,
+        target: LargeBodyId,
+        t: Time,
+    ) -> Option<TransferPlan> {
+        let parent = self.parent(origin.into())?;
+        if self.parent(target.into()) != Some(parent) || origin == target {
+            return None;
+        }
+        let parent = self.get_large_body(parent);
+
+        let origin_orbit = self.get_large_body(origin).orbit().as_ref()?;
+        let target_orbit = self.get_large_body(target).orbit().as_ref()?;
+        if origin_orbit.is_anticlockwise() != target_orbit.is_anticlockwise() {
+            return None;
+        }
+        let direction = if origin_orbit.is_anticlockwise() {
+            1.0
+        } else {
+            -1.0
+        };
+
+        let origin_motion = 2.0 * PI / origin_orbit.period()?;
+        let target_motion = 2.0 * PI / target_orbit.period()?;
+        let origin_radius = Length(origin_orbit.semi_major_axis() as f32);
+        let target_radius = Length(target_orbit.semi_major_axis() as f32);
+
+        let angle = |position: Vector| f64::from(position.y.atan2(position.x));
+        let phase_angle = (direction
+            * (angle(target_orbit.position(t)) - angle(origin_orbit.position(t))))
+        .rem_euclid(2.0 * PI);
+
+        let hohmann = math::hohmann(parent.mass(), origin_radius, target_radius);
+        // the target must travel to the opposite side of the parent during the transfer
+        let departure_phase_angle =
+            (PI - target_motion * hohmann.time_of_flight()).rem_euclid(2.0 * PI);
+
+        let phase_rate = target_motion - origin_motion;
+        let (next_window, synodic_period) = if phase_rate == 0.0 {
+            (None, None)
+        } else {
+            let wait = if phase_rate > 0.0 {
+                (departure_phase_angle - phase_angle).rem_euclid(2.0 * PI) / phase_rate
+            } else {
+                (phase_angle - departure_phase_angle).rem_euclid(2.0 * PI) / -phase_rate
+            };
+            let next_window = Time(t.0 + wait.ceil() as i32);
+            (Some(next_window), Some(2.0 * PI / phase_rate.abs()))
+        };
+
+        let grav_radius = parent.grav_radius();
+        let bi_elliptic = if grav_radius.0.is_finite() {
+            Some(math::bi_elliptic(
+                parent.mass(),
+                origin_radius,
+                target_radius,
+                grav_radius,
+            ))
+        } else {
+            None
+        };
+
+        Some(TransferPlan {
+            phase_angle,
+            departure_phase_angle,
+            next_window,
+            synodic_period,
+            hohmann,
+            bi_elliptic,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Eci, Orbit, GRAVITY};
+
+    const MASS: f64 = 1e6;
+
+    /// Two planets on circular orbits, with the outer one 0.5 rad ahead
+    fn system() -> System<()> {
+        let schema: LargeBodySchema = serde_json::from_value(serde_json::json!({
+            "surface_radius": 100.0,
+            "grav_radius": 10000.0,
+            "mass": MASS,
+            "children": [{
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2000.0, 0.0], "velocity": [0.0, 22.36]},
+            }, {
+                "surface_radius": 10.0,
+                "grav_radius": 300.0,
+                "mass": 1000.0,
+                "eci": {"position": [2632.7, 1438.3], "velocity": [-8.753, 16.022]},
+            }],
+        }))
+        .expect("Invalid schema");
+        System::from_schema(schema, ())
+    }
+
+    #[test]
+    fn test_hohmann_window() {
+        let system = system();
+        let tree = system.tree();
+        let planets = tree.root().children();
+        let (origin, target) = (planets[0].borrow_large(), planets[1].borrow_large());
+        let plan = tree
+            .plan_transfer(origin.id(), target.id(), Time(0))
+            .expect("Planets are siblings");
+
+        assert!((plan.phase_angle() - 0.5).abs() < 1e-3);
+        let motion = |radius: f64| (GRAVITY * MASS / radius.powi(3)).sqrt();
+        let synodic_period = 2.0 * PI / (motion(2000.0) - motion(3000.0));
+        let period = plan
+            .synodic_period()
+            .expect("Orbits have different periods");
+        assert!((period - synodic_period).abs() < 1e-3 * synodic_period);
+        let tof = plan.hohmann().time_of_flight();
+        assert!((plan.departure_phase_angle() - (PI - motion(3000.0) * tof)).abs() < 1e-3);
+
+        let departure = plan.next_window().expect("Orbits have different periods");
+        assert!(departure.0 > 0 && f64::from(departure.0) < period);
+        let at_departure = tree
+            .plan_transfer(origin.id(), target.id(), departure)
+            .expect("Planets are siblings");
+        assert!((at_departure.phase_angle() - plan.departure_phase_angle()).abs() < 1e-2);
+
+        // a prograde burn at the window reaches the target after the time of flight
+        let eci = origin.orbit().as_ref().unwrap().eci(departure);
+        let speed = eci.velocity().norm();
+        let burn = plan.hohmann().burns()[0] as f32;
+        let eci = Eci::new(eci.position(), eci.velocity() * ((speed + burn) / speed));
+        let transfer = Orbit::from_mpv(tree.root().mass(), eci, departure);
+        let arrival = Time(departure.0 + tof.round() as i32);
+        let target_position = target.orbit().as_ref().unwrap().position(arrival);
+        let miss = (transfer.position(arrival) - target_position).norm();
+        assert!(miss < 10.0, "missed the target by {}", miss);
+    }
+
+    #[test]
+    fn test_not_siblings() {
+        let system = system();
+        let tree = system.tree();
+        let root = tree.root().id();
+        let planet = tree.root().children()[0].borrow_large().id();
+        assert!(tree.plan_transfer(root, planet, Time(0)).is_none());
+        assert!(tree.plan_transfer(planet, planet, Time(0)).is_none());
+    }
+}